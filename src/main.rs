@@ -1,5 +1,40 @@
-use std::io;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const APP_DIR: &str = "rust_todo_cli";
+const DATA_FILE: &str = "tasks.json";
+const LISTS_DIR: &str = "lists";
+const DEFAULT_LIST: &str = "default";
+
+fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("TODO_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+    base.map(|base| base.join(APP_DIR))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+fn data_file_path() -> PathBuf {
+    let dir = data_dir();
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("Warning: could not create {}: {}", dir.display(), e);
+    }
+    dir.join(DATA_FILE)
+}
 
 mod id_generation {
     use std::sync::atomic::{AtomicU32, Ordering};
@@ -9,14 +44,689 @@ mod id_generation {
     pub fn next() -> u32 {
         COUNTER.fetch_add(1, Ordering::Relaxed)
     }
+
+    pub fn set_floor(value: u32) {
+        COUNTER.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Lowers the counter too; only for renumbering, when no higher id is in use.
+    pub fn reset(value: u32) {
+        COUNTER.store(value, Ordering::Relaxed);
+    }
+}
+
+mod json {
+    use std::fmt::Write;
+
+    pub enum Value {
+        Null,
+        Bool(bool),
+        Number(f64),
+        String(String),
+        Array(Vec<Value>),
+        Object(Vec<(String, Value)>),
+    }
+    impl Value {
+        pub fn get(&self, key: &str) -> Option<&Value> {
+            match self {
+                Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+
+        pub fn as_str(&self) -> Option<&str> {
+            match self {
+                Value::String(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        pub fn as_u64(&self) -> Option<u64> {
+            match self {
+                Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as u64),
+                _ => None,
+            }
+        }
+
+        pub fn as_bool(&self) -> Option<bool> {
+            match self {
+                Value::Bool(b) => Some(*b),
+                _ => None,
+            }
+        }
+
+        pub fn as_array(&self) -> Option<&[Value]> {
+            match self {
+                Value::Array(items) => Some(items),
+                _ => None,
+            }
+        }
+    }
+
+    pub fn stringify(value: &Value) -> String {
+        let mut out = String::new();
+        write_value(&mut out, value, None);
+        out
+    }
+
+    pub fn stringify_pretty(value: &Value) -> String {
+        let mut out = String::new();
+        write_value(&mut out, value, Some(0));
+        out.push('\n');
+        out
+    }
+
+    fn write_newline(out: &mut String, indent: Option<usize>) {
+        if let Some(depth) = indent {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        }
+    }
+
+    fn write_value(out: &mut String, value: &Value, indent: Option<usize>) {
+        let inner = indent.map(|depth| depth + 1);
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => {
+                let _ = write!(out, "{}", n);
+            }
+            Value::String(s) => write_string(out, s),
+            Value::Array(items) if items.is_empty() => out.push_str("[]"),
+            Value::Array(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_newline(out, inner);
+                    write_value(out, item, inner);
+                }
+                write_newline(out, indent);
+                out.push(']');
+            }
+            Value::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Value::Object(fields) => {
+                out.push('{');
+                for (i, (key, item)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_newline(out, inner);
+                    write_string(out, key);
+                    out.push(':');
+                    if indent.is_some() {
+                        out.push(' ');
+                    }
+                    write_value(out, item, inner);
+                }
+                write_newline(out, indent);
+                out.push('}');
+            }
+        }
+    }
+
+    fn write_string(out: &mut String, s: &str) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+
+    pub fn parse(input: &str) -> Result<Value, String> {
+        let mut parser = Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(format!(
+                "Unexpected trailing data at position {}.",
+                parser.pos
+            ));
+        }
+        Ok(value)
+    }
+
+    struct Parser {
+        chars: Vec<char>,
+        pos: usize,
+    }
+    impl Parser {
+        fn peek(&self) -> Option<char> {
+            self.chars.get(self.pos).copied()
+        }
+
+        fn next(&mut self) -> Option<char> {
+            let c = self.peek();
+            self.pos += 1;
+            c
+        }
+
+        fn skip_whitespace(&mut self) {
+            while matches!(self.peek(), Some(' ' | '\n' | '\r' | '\t')) {
+                self.pos += 1;
+            }
+        }
+
+        fn expect(&mut self, expected: char) -> Result<(), String> {
+            match self.next() {
+                Some(c) if c == expected => Ok(()),
+                _ => Err(format!(
+                    "Expected '{}' at position {}.",
+                    expected,
+                    self.pos - 1
+                )),
+            }
+        }
+
+        fn parse_literal(&mut self, literal: &str, value: Value) -> Result<Value, String> {
+            for expected in literal.chars() {
+                self.expect(expected)?;
+            }
+            Ok(value)
+        }
+
+        fn parse_value(&mut self) -> Result<Value, String> {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('n') => self.parse_literal("null", Value::Null),
+                Some('t') => self.parse_literal("true", Value::Bool(true)),
+                Some('f') => self.parse_literal("false", Value::Bool(false)),
+                Some('"') => self.parse_string().map(Value::String),
+                Some('[') => self.parse_array(),
+                Some('{') => self.parse_object(),
+                Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+                Some(c) => Err(format!(
+                    "Unexpected character '{}' at position {}.",
+                    c, self.pos
+                )),
+                None => Err("Unexpected end of input.".to_string()),
+            }
+        }
+
+        fn parse_number(&mut self) -> Result<Value, String> {
+            let start = self.pos;
+            while matches!(self.peek(), Some(c) if c.is_ascii_digit() || "+-.eE".contains(c)) {
+                self.pos += 1;
+            }
+            let text: String = self.chars[start..self.pos].iter().collect();
+            text.parse::<f64>()
+                .map(Value::Number)
+                .map_err(|_| format!("Invalid number '{}' at position {}.", text, start))
+        }
+
+        fn parse_hex4(&mut self) -> Result<u32, String> {
+            let mut code = 0;
+            for _ in 0..4 {
+                let digit = self.next().and_then(|c| c.to_digit(16)).ok_or_else(|| {
+                    format!("Invalid unicode escape at position {}.", self.pos - 1)
+                })?;
+                code = code * 16 + digit;
+            }
+            Ok(code)
+        }
+
+        fn parse_string(&mut self) -> Result<String, String> {
+            self.expect('"')?;
+            let mut out = String::new();
+            loop {
+                match self.next() {
+                    Some('"') => return Ok(out),
+                    Some('\\') => match self.next() {
+                        Some('"') => out.push('"'),
+                        Some('\\') => out.push('\\'),
+                        Some('/') => out.push('/'),
+                        Some('b') => out.push('\u{8}'),
+                        Some('f') => out.push('\u{c}'),
+                        Some('n') => out.push('\n'),
+                        Some('r') => out.push('\r'),
+                        Some('t') => out.push('\t'),
+                        Some('u') => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => return Err(format!("Invalid escape at position {}.", self.pos - 1)),
+                    },
+                    Some(c) => out.push(c),
+                    None => return Err("Unterminated string.".to_string()),
+                }
+            }
+        }
+
+        fn parse_array(&mut self) -> Result<Value, String> {
+            self.expect('[')?;
+            let mut items = Vec::new();
+            self.skip_whitespace();
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(self.parse_value()?);
+                self.skip_whitespace();
+                match self.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err(format!("Expected ',' or ']' at position {}.", self.pos - 1)),
+                }
+            }
+        }
+
+        fn parse_object(&mut self) -> Result<Value, String> {
+            self.expect('{')?;
+            let mut fields = Vec::new();
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(Value::Object(fields));
+            }
+            loop {
+                self.skip_whitespace();
+                let key = self.parse_string()?;
+                self.skip_whitespace();
+                self.expect(':')?;
+                let value = self.parse_value()?;
+                fields.push((key, value));
+                self.skip_whitespace();
+                match self.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Object(fields)),
+                    _ => {
+                        return Err(format!(
+                            "Expected ',' or '}}' at position {}.",
+                            self.pos - 1
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+mod ansi {
+    pub const RED: &str = "\x1b[31m";
+    pub const GREEN: &str = "\x1b[32m";
+    pub const YELLOW: &str = "\x1b[33m";
+    const RESET: &str = "\x1b[0m";
+
+    pub fn named(name: &str) -> Option<&'static str> {
+        match name.trim().to_lowercase().as_str() {
+            "red" => Some(RED),
+            "green" => Some(GREEN),
+            "yellow" => Some(YELLOW),
+            "blue" => Some("\x1b[34m"),
+            "magenta" | "purple" => Some("\x1b[35m"),
+            "cyan" => Some("\x1b[36m"),
+            _ => None,
+        }
+    }
+
+    pub fn paint(text: &str, color: &str) -> String {
+        format!("{}{}{}", color, text, RESET)
+    }
+}
+
+mod csv {
+    pub fn escape_field(field: &str) -> String {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    }
+
+    pub fn write_record(fields: &[&str]) -> String {
+        let fields: Vec<String> = fields.iter().map(|field| escape_field(field)).collect();
+        format!("{}\r\n", fields.join(","))
+    }
+
+    /// Parses CSV content into records, each tagged with the line it starts on.
+    pub fn parse(content: &str) -> Result<Vec<(usize, Vec<String>)>, String> {
+        let mut records = Vec::new();
+        let mut chars = content.chars().peekable();
+        let mut line = 1;
+
+        while chars.peek().is_some() {
+            let start_line = line;
+            let mut fields = Vec::new();
+            let mut field = String::new();
+            let mut quoted = false;
+
+            loop {
+                match chars.next() {
+                    Some('"') if quoted => {
+                        if chars.peek() == Some(&'"') {
+                            chars.next();
+                            field.push('"');
+                        } else {
+                            quoted = false;
+                        }
+                    }
+                    Some('"') if field.is_empty() => quoted = true,
+                    Some(c) if quoted => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        field.push(c);
+                    }
+                    Some(',') => fields.push(std::mem::take(&mut field)),
+                    Some('\r') if chars.peek() == Some(&'\n') => {}
+                    Some('\n') => {
+                        line += 1;
+                        break;
+                    }
+                    Some(c) => field.push(c),
+                    None if quoted => {
+                        return Err(format!("Unterminated quoted field on line {}.", start_line));
+                    }
+                    None => break,
+                }
+            }
+            fields.push(field);
+
+            if !(fields.len() == 1 && fields[0].is_empty()) {
+                records.push((start_line, fields));
+            }
+        }
+        Ok(records)
+    }
+}
+
+mod date_utils {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    pub const SECS_PER_DAY: u64 = 86_400;
+
+    pub fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time")
+            .as_secs()
+    }
+
+    /// Converts days since 1970-01-01 into a (year, month, day) civil date.
+    pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+
+    /// Converts a (year, month, day) civil date into days since 1970-01-01.
+    pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let mp = if month > 2 { month - 3 } else { month + 9 } as i64;
+        let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    fn days_in_month(year: i64, month: u32) -> u32 {
+        match month {
+            2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Parses a strict "YYYY-MM-DD" date into days since 1970-01-01.
+    pub fn parse_date(input: &str) -> Option<i64> {
+        let bytes = input.as_bytes();
+        if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+            return None;
+        }
+        let year: i64 = input[0..4].parse().ok()?;
+        let month: u32 = input[5..7].parse().ok()?;
+        let day: u32 = input[8..10].parse().ok()?;
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(days_from_civil(year, month, day))
+    }
+
+    pub fn today() -> i64 {
+        (now_secs() / SECS_PER_DAY) as i64
+    }
+
+    pub fn format_date(days: i64) -> String {
+        let (year, month, day) = civil_from_days(days);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    /// Moves a date forward by whole months, clamping to the end of shorter months.
+    pub fn add_months(days: i64, months: u32) -> i64 {
+        let (year, month, day) = civil_from_days(days);
+        let index = year * 12 + (month as i64 - 1) + months as i64;
+        let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+        days_from_civil(year, month, day.min(days_in_month(year, month)))
+    }
+
+    /// Formats epoch seconds as a UTC "YYYY-MM-DD HH:MM" string.
+    pub fn format_datetime(secs: u64) -> String {
+        let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+        let minutes = (secs % SECS_PER_DAY) / 60;
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+
+    /// Parses a "YYYY-MM-DD HH:MM" string into epoch seconds.
+    pub fn parse_datetime(input: &str) -> Option<u64> {
+        let (date, time) = input.split_once(' ')?;
+        let (hours, minutes) = time.split_once(':')?;
+        let (hours, minutes): (u64, u64) = (hours.parse().ok()?, minutes.parse().ok()?);
+        if hours > 23 || minutes > 59 {
+            return None;
+        }
+        let days = u64::try_from(parse_date(date)?).ok()?;
+        Some(days * SECS_PER_DAY + hours * 3_600 + minutes * 60)
+    }
+
+    /// Checks that a format string only uses the supported %Y %m %d %H %M %% specifiers.
+    pub fn is_valid_format(format: &str) -> bool {
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c == '%' && !matches!(chars.next(), Some('Y' | 'm' | 'd' | 'H' | 'M' | '%')) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Formats epoch seconds using a format string accepted by `is_valid_format`.
+    pub fn format_with(secs: u64, format: &str) -> String {
+        let (year, month, day) = civil_from_days((secs / SECS_PER_DAY) as i64);
+        let minutes = (secs % SECS_PER_DAY) / 60;
+        let mut out = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => out.push_str(&format!("{:04}", year)),
+                Some('m') => out.push_str(&format!("{:02}", month)),
+                Some('d') => out.push_str(&format!("{:02}", day)),
+                Some('H') => out.push_str(&format!("{:02}", minutes / 60)),
+                Some('M') => out.push_str(&format!("{:02}", minutes % 60)),
+                Some(other) => out.push(other),
+                None => out.push('%'),
+            }
+        }
+        out
+    }
+
+    /// Parses a due moment; a bare date means the very end of that day.
+    pub fn parse_due(input: &str) -> Option<u64> {
+        if let Some(secs) = parse_datetime(input) {
+            return Some(secs);
+        }
+        let days = u64::try_from(parse_date(input)?).ok()?;
+        Some((days + 1) * SECS_PER_DAY - 1)
+    }
+
+    pub fn relative_age(then: u64, now: u64) -> String {
+        let delta = now.saturating_sub(then);
+        let (count, unit) = match delta {
+            0..60 => return "just now".to_string(),
+            60..3_600 => (delta / 60, "minute"),
+            3_600..SECS_PER_DAY => (delta / 3_600, "hour"),
+            _ => (delta / SECS_PER_DAY, "day"),
+        };
+        let plural = if count == 1 { "" } else { "s" };
+        format!("{} {}{} ago", count, unit, plural)
+    }
+
+    /// Parses a duration such as "2h", "3d" or "1w". Zero amounts are rejected.
+    pub fn parse_duration(input: &str) -> Option<std::time::Duration> {
+        let input = input.trim().to_lowercase();
+        let unit = match input.chars().last()? {
+            'h' => 3_600,
+            'd' => SECS_PER_DAY,
+            'w' => 7 * SECS_PER_DAY,
+            _ => return None,
+        };
+        let count: u64 = input[..input.len() - 1]
+            .parse()
+            .ok()
+            .filter(|&count| count > 0)?;
+        Some(std::time::Duration::from_secs(count.checked_mul(unit)?))
+    }
+
+    pub fn format_minutes(minutes: u32) -> String {
+        match (minutes / 60, minutes % 60) {
+            (0, m) => format!("{}m", m),
+            (h, 0) => format!("{}h", h),
+            (h, m) => format!("{}h {}m", h, m),
+        }
+    }
+
+    /// Renders a stored date, converting files written with raw epoch seconds.
+    pub fn display(date: &str) -> String {
+        match date.parse::<u64>() {
+            Ok(secs) => format_datetime(secs),
+            Err(_) => date.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+impl Priority {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "l" | "low" => Some(Priority::Low),
+            "m" | "medium" => Some(Priority::Medium),
+            "h" | "high" => Some(Priority::High),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Recurrence {
+    Daily,
+    Weekly,
+    Monthly,
+}
+impl Recurrence {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "d" | "daily" => Some(Recurrence::Daily),
+            "w" | "weekly" => Some(Recurrence::Weekly),
+            "m" | "monthly" => Some(Recurrence::Monthly),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Recurrence::Daily => "daily",
+            Recurrence::Weekly => "weekly",
+            Recurrence::Monthly => "monthly",
+        }
+    }
+
+    fn advance(&self, days: i64) -> i64 {
+        match self {
+            Recurrence::Daily => days + 1,
+            Recurrence::Weekly => days + 7,
+            Recurrence::Monthly => date_utils::add_months(days, 1),
+        }
+    }
 }
 
+#[derive(Clone)]
 struct Task {
     id: u32,
     title: String,
     description: String,
     date: String,
     done: bool,
+    due_date: Option<String>,
+    priority: Priority,
+    tags: Vec<String>,
+    recurrence: Option<Recurrence>,
+    created_at: u64,
+    notes: Vec<String>,
+    estimate_minutes: Option<u32>,
+    blocked_by: Option<u32>,
+    next_occurrence_id: Option<u32>,
+    completed_at: Option<u64>,
+    color_label: Option<String>,
+    subtasks: Vec<(String, bool)>,
+    link: Option<String>,
+    pinned: bool,
+    snooze_count: u32,
 }
 impl Task {
     fn new(title: String, description: String, date: String, done: bool) -> Self {
@@ -26,191 +736,6178 @@ impl Task {
             description,
             date,
             done,
+            due_date: None,
+            priority: Priority::default(),
+            tags: Vec::new(),
+            recurrence: None,
+            created_at: date_utils::now_secs(),
+            notes: Vec::new(),
+            estimate_minutes: None,
+            blocked_by: None,
+            next_occurrence_id: None,
+            completed_at: None,
+            color_label: None,
+            subtasks: Vec::new(),
+            link: None,
+            pinned: false,
+            snooze_count: 0,
         }
     }
-}
 
+    fn next_occurrence(&self) -> Option<Task> {
+        let recurrence = self.recurrence?;
+        let due = self.due_at();
+        let day = due.map_or_else(date_utils::today, |secs| {
+            (secs / date_utils::SECS_PER_DAY) as i64
+        });
+        let next_day = recurrence.advance(day);
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        let mut task = Task::new(self.title.clone(), self.description.clone(), date, false);
+        task.due_date = Some(match due {
+            Some(secs) if self.has_due_time() => date_utils::format_datetime(
+                next_day as u64 * date_utils::SECS_PER_DAY + secs % date_utils::SECS_PER_DAY,
+            ),
+            _ => date_utils::format_date(next_day),
+        });
+        task.priority = self.priority;
+        task.tags = self.tags.clone();
+        task.recurrence = Some(recurrence);
+        task.estimate_minutes = self.estimate_minutes;
+        task.color_label = self.color_label.clone();
+        task.subtasks = self
+            .subtasks
+            .iter()
+            .map(|(title, _)| (title.clone(), false))
+            .collect();
+        task.link = self.link.clone();
+        Some(task)
+    }
 
-struct TasksModel {
-    tasks: Vec<Task>,
-}
-impl TasksModel {
-    fn new() -> Self {
-        Self { tasks: Vec::new() }
+    fn due_at(&self) -> Option<u64> {
+        self.due_date.as_deref().and_then(date_utils::parse_due)
     }
-    
-    pub fn add(&mut self, item: Task) {
-        self.tasks.push(item);
+
+    fn has_due_time(&self) -> bool {
+        self.due_date
+            .as_deref()
+            .is_some_and(|due| date_utils::parse_datetime(due).is_some())
     }
 
-    pub fn get_all(&self) -> &[Task] {
-        &self.tasks
+    fn is_overdue(&self, now: u64) -> bool {
+        !self.done && self.due_at().is_some_and(|due| due < now)
     }
 
-    pub fn delete_all(&mut self) {
-        self.tasks.clear();
+    /// Pending and due within `window` seconds, but not yet overdue.
+    fn is_due_soon(&self, now: u64, window: u64) -> bool {
+        !self.done && !self.is_overdue(now) && self.due_at().is_some_and(|due| due <= now + window)
     }
 
-    pub fn delete(&mut self, id: u32) -> Result<(), String> {
-        if let Some(i) = self.tasks.iter().position(|item| item.id == id) {
-            self.tasks.remove(i);
-            Ok(())
-        } else {
-            Err(format!("Task with id {} not found.", id))
-        }
+    /// Returns `(done, total)` subtask counts.
+    fn subtask_progress(&self) -> (usize, usize) {
+        let done = self.subtasks.iter().filter(|(_, done)| *done).count();
+        (done, self.subtasks.len())
     }
 
-    pub fn toggle(&mut self, id: u32) -> Result<(), String> {
-        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
-            item.done = !item.done;
-            Ok(())
-        } else {
-            Err(format!("Task with id {} not found.", id))
-        }
+    fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim().to_lowercase();
+        self.tags.iter().any(|item| item.to_lowercase() == tag)
     }
-}
 
+    fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("id".to_string(), json::Value::Number(self.id as f64)),
+            ("title".to_string(), json::Value::String(self.title.clone())),
+            (
+                "description".to_string(),
+                json::Value::String(self.description.clone()),
+            ),
+            ("date".to_string(), json::Value::String(self.date.clone())),
+            ("done".to_string(), json::Value::Bool(self.done)),
+            ("pinned".to_string(), json::Value::Bool(self.pinned)),
+            (
+                "due_date".to_string(),
+                match &self.due_date {
+                    Some(due) => json::Value::String(due.clone()),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "priority".to_string(),
+                json::Value::String(self.priority.as_str().to_string()),
+            ),
+            (
+                "tags".to_string(),
+                json::Value::Array(
+                    self.tags
+                        .iter()
+                        .map(|tag| json::Value::String(tag.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "recurrence".to_string(),
+                match self.recurrence {
+                    Some(recurrence) => json::Value::String(recurrence.as_str().to_string()),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "created_at".to_string(),
+                json::Value::Number(self.created_at as f64),
+            ),
+            (
+                "estimate_minutes".to_string(),
+                match self.estimate_minutes {
+                    Some(minutes) => json::Value::Number(minutes as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "blocked_by".to_string(),
+                match self.blocked_by {
+                    Some(id) => json::Value::Number(id as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "next_occurrence_id".to_string(),
+                match self.next_occurrence_id {
+                    Some(id) => json::Value::Number(id as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "completed_at".to_string(),
+                match self.completed_at {
+                    Some(secs) => json::Value::Number(secs as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "color_label".to_string(),
+                match &self.color_label {
+                    Some(label) => json::Value::String(label.clone()),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "snooze_count".to_string(),
+                json::Value::Number(self.snooze_count as f64),
+            ),
+            (
+                "link".to_string(),
+                match &self.link {
+                    Some(link) => json::Value::String(link.clone()),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "notes".to_string(),
+                json::Value::Array(
+                    self.notes
+                        .iter()
+                        .map(|note| json::Value::String(note.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "subtasks".to_string(),
+                json::Value::Array(
+                    self.subtasks
+                        .iter()
+                        .map(|(title, done)| {
+                            json::Value::Object(vec![
+                                ("title".to_string(), json::Value::String(title.clone())),
+                                ("done".to_string(), json::Value::Bool(*done)),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ),
+        ])
+    }
 
-struct CliView;
-impl CliView {
-    fn new() -> Self {
-        Self {}
+    fn from_json(value: &json::Value) -> Option<Self> {
+        let date = value.get("date")?.as_str()?.to_string();
+        let created_at = value
+            .get("created_at")
+            .and_then(json::Value::as_u64)
+            .or_else(|| date.parse::<u64>().ok())
+            .or_else(|| date_utils::parse_datetime(&date))
+            .unwrap_or(0);
+        Some(Self {
+            id: value.get("id")?.as_u64()? as u32,
+            title: value.get("title")?.as_str()?.to_string(),
+            description: value.get("description")?.as_str()?.to_string(),
+            date,
+            done: value.get("done")?.as_bool()?,
+            pinned: value
+                .get("pinned")
+                .and_then(json::Value::as_bool)
+                .unwrap_or(false),
+            due_date: value
+                .get("due_date")
+                .and_then(json::Value::as_str)
+                .map(str::to_string),
+            priority: value
+                .get("priority")
+                .and_then(json::Value::as_str)
+                .and_then(Priority::parse)
+                .unwrap_or_default(),
+            tags: value
+                .get("tags")
+                .and_then(json::Value::as_array)
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(json::Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            recurrence: value
+                .get("recurrence")
+                .and_then(json::Value::as_str)
+                .and_then(Recurrence::parse),
+            created_at,
+            estimate_minutes: value
+                .get("estimate_minutes")
+                .and_then(json::Value::as_u64)
+                .map(|minutes| minutes as u32),
+            blocked_by: value
+                .get("blocked_by")
+                .and_then(json::Value::as_u64)
+                .map(|id| id as u32),
+            next_occurrence_id: value
+                .get("next_occurrence_id")
+                .and_then(json::Value::as_u64)
+                .map(|id| id as u32),
+            completed_at: value.get("completed_at").and_then(json::Value::as_u64),
+            color_label: value
+                .get("color_label")
+                .and_then(json::Value::as_str)
+                .map(str::to_string),
+            snooze_count: value
+                .get("snooze_count")
+                .and_then(json::Value::as_u64)
+                .unwrap_or(0) as u32,
+            link: value
+                .get("link")
+                .and_then(json::Value::as_str)
+                .map(str::to_string),
+            notes: value
+                .get("notes")
+                .and_then(json::Value::as_array)
+                .map(|notes| {
+                    notes
+                        .iter()
+                        .filter_map(json::Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            subtasks: value
+                .get("subtasks")
+                .and_then(json::Value::as_array)
+                .map(|subtasks| {
+                    subtasks
+                        .iter()
+                        .filter_map(|item| {
+                            let title = item.get("title")?.as_str()?.to_string();
+                            Some((title, item.get("done")?.as_bool()?))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
     }
+}
 
-    pub fn show_menu(&self) {
-        println!(
-            r#"
-******************************************
-*              TODO LIST                 *
-******************************************
-1. Show all tasks
-2. Add a task
-3. Delete a task
-4. Toggle task status
-5. Clear all
-0. Exit
-******************************************
-"#
-        );
-    }
-
-    pub fn display_tasks(&self, tasks: &[Task]) {
-        if tasks.is_empty() {
-            println!("Todo list is empty.");
-            return;
+/// The reusable parts of a task, saved under a name.
+struct TaskTemplate {
+    title: String,
+    description: String,
+    priority: Priority,
+    tags: Vec<String>,
+    estimate_minutes: Option<u32>,
+    color_label: Option<String>,
+}
+impl TaskTemplate {
+    fn from_task(task: &Task) -> Self {
+        Self {
+            title: task.title.clone(),
+            description: task.description.clone(),
+            priority: task.priority,
+            tags: task.tags.clone(),
+            estimate_minutes: task.estimate_minutes,
+            color_label: task.color_label.clone(),
         }
-        println!("Your tasks");
-        println!("******************************************");
-        for task in tasks {
-            let status = if task.done { "✓ Done" } else { "✗ Not done" };
+    }
 
-            let description = if !task.description.trim().is_empty() {
-                format!(" 📝 {:<40}\n", task.description.trim())
-            } else {
-                String::new()
-            };
-            println!(
-                "id: {} | status: {} | title: {}\n{} 📅 {}\n",
-                task.id,
-                status,
-                task.title.trim(),
-                description,
-                task.date
-            );
-            println!("******************************************");
-        }
+    /// Creates a fresh task due today.
+    fn instantiate(&self) -> Task {
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        let mut task = Task::new(self.title.clone(), self.description.clone(), date, false);
+        task.due_date = Some(date_utils::format_date(date_utils::today()));
+        task.priority = self.priority;
+        task.tags = self.tags.clone();
+        task.estimate_minutes = self.estimate_minutes;
+        task.color_label = self.color_label.clone();
+        task
     }
 
-    pub fn get_user_input(&self, prompt: &str) -> String {
-        println!("{}", prompt);
-        let mut input = String::new();
-        let _ = io::stdin().read_line(&mut input).expect("Failed to read.");
-        input.trim().to_string()
+    fn to_json(&self) -> json::Value {
+        json::Value::Object(vec![
+            ("title".to_string(), json::Value::String(self.title.clone())),
+            (
+                "description".to_string(),
+                json::Value::String(self.description.clone()),
+            ),
+            (
+                "priority".to_string(),
+                json::Value::String(self.priority.as_str().to_string()),
+            ),
+            (
+                "tags".to_string(),
+                json::Value::Array(
+                    self.tags
+                        .iter()
+                        .map(|tag| json::Value::String(tag.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "estimate_minutes".to_string(),
+                match self.estimate_minutes {
+                    Some(minutes) => json::Value::Number(minutes as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "color_label".to_string(),
+                match &self.color_label {
+                    Some(label) => json::Value::String(label.clone()),
+                    None => json::Value::Null,
+                },
+            ),
+        ])
+    }
+
+    fn from_json(value: &json::Value) -> Option<Self> {
+        Some(Self {
+            title: value.get("title")?.as_str()?.to_string(),
+            description: value
+                .get("description")
+                .and_then(json::Value::as_str)
+                .unwrap_or_default()
+                .to_string(),
+            priority: value
+                .get("priority")
+                .and_then(json::Value::as_str)
+                .and_then(Priority::parse)
+                .unwrap_or_default(),
+            tags: value
+                .get("tags")
+                .and_then(json::Value::as_array)
+                .map(|tags| {
+                    tags.iter()
+                        .filter_map(json::Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            estimate_minutes: value
+                .get("estimate_minutes")
+                .and_then(json::Value::as_u64)
+                .map(|minutes| minutes as u32),
+            color_label: value
+                .get("color_label")
+                .and_then(json::Value::as_str)
+                .map(str::to_string),
+        })
     }
 }
 
+const FORMAT_VERSION: u64 = 1;
 
-struct Presenter {
-    model: TasksModel,
-    view: CliView,
+struct TasksModel {
+    tasks: Vec<Task>,
+    archived: Vec<Task>,
+    templates: HashMap<String, TaskTemplate>,
+    last_opened: Option<u64>,
+    path: PathBuf,
+    read_only: bool,
+    dirty: bool,
+    max_tasks: usize,
 }
-impl Presenter {
-    pub fn new(model: TasksModel, view: CliView) -> Self {
-        Self { model, view }
+impl TasksModel {
+    fn load_from(path: impl Into<PathBuf>) -> Self {
+        let mut model = Self {
+            tasks: Vec::new(),
+            archived: Vec::new(),
+            templates: HashMap::new(),
+            last_opened: None,
+            path: path.into(),
+            read_only: false,
+            dirty: false,
+            max_tasks: 0,
+        };
+        let bytes = fs::read(&model.path).unwrap_or_default();
+        let result = match String::from_utf8(bytes) {
+            Ok(content) if content.trim().is_empty() => Ok(()),
+            Ok(content) => model.load_store(&content),
+            Err(_) => Err("invalid UTF-8".to_string()),
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if model.read_only => {
+                eprintln!("Warning: could not read {}: {}", model.path.display(), e)
+            }
+            Err(_) => model.recover_from_corrupt_file(),
+        }
+        let ids = model
+            .tasks
+            .iter()
+            .chain(&model.archived)
+            .map(|task| task.id);
+        if let Some(max_id) = ids.max() {
+            id_generation::set_floor(max_id + 1);
+        }
+        model
     }
 
-    pub fn interaction_loop(&mut self) {
-        loop {
-            self.view.show_menu();
-
-            let option: u32 = self
-                .view
-                .get_user_input("Select an option:")
-                .parse::<u32>()
-                .unwrap_or(0);
-
-            match option {
-                1 => self.show_tasks(),
-                2 => self.add_task(),
-                3 => self.delete_task(),
-                4 => self.toggle_status(),
-                5 => self.delete_tasks(),
-                0 => break,
-                _ => println!("Invalid option"),
+    /// Moves an unreadable file aside so the next save can't destroy it.
+    fn recover_from_corrupt_file(&mut self) {
+        self.tasks.clear();
+        self.archived.clear();
+        let mut backup = self.path.clone().into_os_string();
+        backup.push(".bak");
+        let backup = PathBuf::from(backup);
+        match fs::rename(&self.path, &backup) {
+            Ok(()) => eprintln!(
+                "Warning: tasks file is corrupt, starting empty. Backup saved to {}",
+                backup.display()
+            ),
+            Err(e) => {
+                self.read_only = true;
+                eprintln!(
+                    "Warning: tasks file is corrupt and could not be backed up ({}); changes will not be saved.",
+                    e
+                );
             }
         }
     }
 
-    pub fn show_tasks(&mut self) {
-        self.view.display_tasks(&self.model.get_all());
+    fn load_store(&mut self, content: &str) -> Result<(), String> {
+        let store = Self::migrate(json::parse(content)?)?;
+        // The first object layout predates the version field.
+        let version = store
+            .get("version")
+            .and_then(json::Value::as_u64)
+            .unwrap_or(1);
+        if version > FORMAT_VERSION {
+            self.read_only = true;
+            return Err(format!(
+                "format version {} is newer than the supported version {}; changes will not be saved",
+                version, FORMAT_VERSION
+            ));
+        }
+        self.tasks = Self::parse_tasks(store.get("tasks").ok_or("expected a list of tasks")?)?;
+        if let Some(archived) = store.get("archived") {
+            self.archived = Self::parse_tasks(archived)?;
+        }
+        self.last_opened = store.get("last_opened").and_then(json::Value::as_u64);
+        if let Some(json::Value::Object(templates)) = store.get("templates") {
+            for (name, value) in templates {
+                let template = TaskTemplate::from_json(value).ok_or("malformed template entry")?;
+                self.templates.insert(name.clone(), template);
+            }
+        }
+        Ok(())
     }
 
-    pub fn add_task(&mut self) {
-        let title = self.view.get_user_input("Enter task title:");
-        let description = self.view.get_user_input("Enter task description:");
-
-        if title.is_empty() || description.is_empty() {
-            return;
+    /// Upgrades older save formats to the current layout. Version 0 was a bare array of tasks.
+    fn migrate(value: json::Value) -> Result<json::Value, String> {
+        match value {
+            json::Value::Array(_) => Ok(json::Value::Object(vec![
+                (
+                    "version".to_string(),
+                    json::Value::Number(FORMAT_VERSION as f64),
+                ),
+                ("tasks".to_string(), value),
+            ])),
+            json::Value::Object(_) => Ok(value),
+            _ => Err("expected a list of tasks".to_string()),
         }
-
-        let now = SystemTime::now();
-        let since_epoch = now
-            .duration_since(UNIX_EPOCH)
-            .expect("System time");
-
-        let secs_since = since_epoch.as_secs().to_string();
-        let task = Task::new(title, description, secs_since, false);
-        self.model.add(task);
     }
 
-    pub fn delete_task(&mut self) {
-        let input: String = self.view.get_user_input("Enter task id to delete:");
-        input
-            .parse::<u32>()
-            .ok()
-            .and_then(|id| self.model.delete(id).err().map(|e| println!("{}", e)));
+    fn parse_tasks(value: &json::Value) -> Result<Vec<Task>, String> {
+        value
+            .as_array()
+            .ok_or("expected a list of tasks")?
+            .iter()
+            .map(|item| Task::from_json(item).ok_or_else(|| "malformed task entry".to_string()))
+            .collect()
     }
 
-    pub fn toggle_status(&mut self) {
-        self.view.display_tasks(&self.model.get_all());
-        let input = self.view.get_user_input("Enter task id to toggle:");
-        input
-            .parse::<u32>()
-            .ok()
-            .and_then(|id| self.model.toggle(id).err().map(|e| println!("{}", e)));
+    fn tasks_json(&self) -> json::Value {
+        json::Value::Array(self.tasks.iter().map(Task::to_json).collect())
     }
 
-    pub fn delete_tasks(&mut self) {
-        self.model.delete_all();
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
     }
-}
 
+    /// Writes pending changes, if any. Callers batch mutations and flush once.
+    pub fn flush(&mut self) -> io::Result<()> {
+        if !self.is_dirty() {
+            return Ok(());
+        }
+        self.save()?;
+        self.dirty = false;
+        Ok(())
+    }
 
+    pub fn save(&self) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::other(
+                "refusing to overwrite a file written by a newer version",
+            ));
+        }
+        self.save_to(&self.path)
+    }
 
-fn main() {
-    let view = CliView::new();
-    let model = TasksModel::new();
-    let mut presenter = Presenter::new(model, view);
+    /// Writes the whole store to `path`; the model keeps its own path and dirty state.
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        let store = json::Value::Object(vec![
+            (
+                "version".to_string(),
+                json::Value::Number(FORMAT_VERSION as f64),
+            ),
+            ("tasks".to_string(), self.tasks_json()),
+            (
+                "last_opened".to_string(),
+                match self.last_opened {
+                    Some(secs) => json::Value::Number(secs as f64),
+                    None => json::Value::Null,
+                },
+            ),
+            (
+                "archived".to_string(),
+                json::Value::Array(self.archived.iter().map(Task::to_json).collect()),
+            ),
+            (
+                "templates".to_string(),
+                json::Value::Object(
+                    self.template_names()
+                        .into_iter()
+                        .map(|name| (name.to_string(), self.templates[name].to_json()))
+                        .collect(),
+                ),
+            ),
+        ]);
+        Self::write_atomically(path, &json::stringify(&store))
+    }
+
+    /// Writes to a sibling temp file and renames it over `path`, so a crash
+    /// mid-write leaves the old file intact.
+    fn write_atomically(path: &Path, content: &str) -> io::Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        if let Err(e) = fs::write(&temp, content) {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        let result = match fs::rename(&temp, path) {
+            // Some Windows setups refuse to rename over an existing file.
+            Err(_) if cfg!(windows) && path.exists() => {
+                fs::remove_file(path).and_then(|()| fs::rename(&temp, path))
+            }
+            result => result,
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
+
+    pub fn export_json(&self, path: &str, pretty: bool) -> io::Result<()> {
+        let value = self.tasks_json();
+        let content = if pretty {
+            json::stringify_pretty(&value)
+        } else {
+            json::stringify(&value)
+        };
+        fs::write(path, content)
+    }
+
+    pub fn add(&mut self, item: Task) {
+        self.dirty = true;
+        self.tasks.push(item);
+    }
+
+    /// Like `add`, but refuses to go past the configured `max_tasks`.
+    pub fn add_task(&mut self, item: Task) -> Result<(), String> {
+        self.check_capacity(1)?;
+        self.add(item);
+        Ok(())
+    }
+
+    /// Checks that `extra` more tasks fit under `max_tasks` (0 means no limit).
+    pub fn check_capacity(&self, extra: usize) -> Result<(), String> {
+        if self.max_tasks == 0 || self.tasks.len() + extra <= self.max_tasks {
+            return Ok(());
+        }
+        if extra == 1 {
+            Err(format!(
+                "You have reached your configured limit of {} tasks. Try clearing completed tasks first.",
+                self.max_tasks
+            ))
+        } else {
+            Err(format!(
+                "Adding {} tasks would go past your configured limit of {} tasks.",
+                extra, self.max_tasks
+            ))
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, item: Task) {
+        let index = index.min(self.tasks.len());
+        self.dirty = true;
+        self.tasks.insert(index, item);
+    }
+
+    pub fn get_all(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn find_by_title(&self, title: &str) -> Option<&Task> {
+        let title = title.trim().to_lowercase();
+        self.tasks
+            .iter()
+            .find(|item| !item.done && item.title.trim().to_lowercase() == title)
+    }
+
+    pub fn filter_by_done(&self, done: bool) -> Vec<&Task> {
+        self.tasks.iter().filter(|item| item.done == done).collect()
+    }
+
+    /// Matches ignore case and diacritics (best-effort, Latin scripts only),
+    /// so "cafe" finds "café".
+    pub fn search(&self, query: &str) -> Vec<&Task> {
+        let query = normalize_for_search(query.trim());
+        if query.is_empty() {
+            return Vec::new();
+        }
+        self.tasks
+            .iter()
+            .filter(|item| {
+                normalize_for_search(&item.title).contains(&query)
+                    || normalize_for_search(&item.description).contains(&query)
+            })
+            .collect()
+    }
+
+    pub fn sorted(&self, order: SortOrder) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.tasks.iter().collect();
+        tasks.sort_by(|a, b| order.compare(a, b));
+        tasks
+    }
+
+    pub fn sorted_by_id(&self) -> Vec<&Task> {
+        self.sorted(SortOrder::Id)
+    }
+
+    pub fn sorted_by_due(&self) -> Vec<&Task> {
+        self.sorted(SortOrder::DueDate)
+    }
+
+    pub fn sorted_by_priority(&self) -> Vec<&Task> {
+        self.sorted(SortOrder::Priority)
+    }
+
+    /// `sorted(order)` with pinned tasks moved to the front, as every listing shows them.
+    pub fn display_order(&self, order: SortOrder) -> Vec<&Task> {
+        let mut tasks = self.sorted(order);
+        tasks.sort_by_key(|item| !item.pinned);
+        tasks
+    }
+
+    pub fn stats(&self) -> (usize, usize, usize) {
+        let total = self.tasks.len();
+        let done = self.tasks.iter().filter(|item| item.done).count();
+        (total, done, total - done)
+    }
+
+    pub fn completed_between(&self, start: u64, end: u64) -> Vec<&Task> {
+        self.tasks
+            .iter()
+            .filter(|item| {
+                item.done
+                    && item
+                        .completed_at
+                        .is_some_and(|secs| (start..end).contains(&secs))
+            })
+            .collect()
+    }
+
+    /// Tasks completed after the previous session ended; empty on first run.
+    pub fn completed_since_last_run(&self) -> Vec<&Task> {
+        match self.last_opened {
+            Some(since) => self.completed_between(since, u64::MAX),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn set_last_opened(&mut self, secs: u64) {
+        // A read-only store can't record it, and shouldn't fail every exit trying.
+        if self.read_only {
+            return;
+        }
+        self.last_opened = Some(secs);
+        self.dirty = true;
+    }
+
+    /// Completions per day for the last `days` days, oldest first, including today.
+    pub fn report_last_days(&self, days: u32) -> Vec<(String, usize)> {
+        let today = date_utils::today();
+        (0..days as i64)
+            .rev()
+            .map(|offset| {
+                let day = today - offset;
+                let start = day as u64 * date_utils::SECS_PER_DAY;
+                let count = self
+                    .completed_between(start, start + date_utils::SECS_PER_DAY)
+                    .len();
+                (date_utils::format_date(day), count)
+            })
+            .collect()
+    }
+
+    pub fn total_estimate(&self) -> u32 {
+        self.tasks
+            .iter()
+            .filter(|item| !item.done)
+            .filter_map(|item| item.estimate_minutes)
+            .sum()
+    }
+
+    pub fn completion_percent(&self) -> u8 {
+        let (total, done, _) = self.stats();
+        if total == 0 {
+            return 0;
+        }
+        ((done * 100 + total / 2) / total) as u8
+    }
+
+    pub fn due_today(&self) -> Vec<&Task> {
+        let today = date_utils::today();
+        self.tasks
+            .iter()
+            .filter(|item| {
+                !item.done
+                    && item
+                        .due_at()
+                        .map(|due| (due / date_utils::SECS_PER_DAY) as i64)
+                        == Some(today)
+            })
+            .collect()
+    }
+
+    pub fn overdue(&self) -> Vec<&Task> {
+        let now = date_utils::now_secs();
+        self.tasks
+            .iter()
+            .filter(|item| item.is_overdue(now))
+            .collect()
+    }
+
+    /// The pending, unblocked task to work on next: highest priority, then soonest due, then oldest id.
+    pub fn next_focus(&self) -> Option<&Task> {
+        self.tasks
+            .iter()
+            .filter(|item| !item.done && open_blocker(item, &self.tasks).is_none())
+            .min_by_key(|item| {
+                let due = item.due_at();
+                (
+                    std::cmp::Reverse(item.priority),
+                    due.is_none(),
+                    due,
+                    item.id,
+                )
+            })
+    }
+
+    /// Tasks due on or between two YYYY-MM-DD dates, soonest first. Invalid bounds match nothing.
+    pub fn due_between(&self, start: &str, end: &str) -> Vec<&Task> {
+        let (Some(start), Some(end)) = (date_utils::parse_date(start), date_utils::parse_date(end))
+        else {
+            return Vec::new();
+        };
+        let mut tasks: Vec<&Task> = self
+            .tasks
+            .iter()
+            .filter(|item| {
+                item.due_at().is_some_and(|secs| {
+                    (start..=end).contains(&((secs / date_utils::SECS_PER_DAY) as i64))
+                })
+            })
+            .collect();
+        tasks.sort_by(|a, b| SortOrder::DueDate.compare(a, b));
+        tasks
+    }
+
+    /// Up to `limit` pending tasks, most snoozed first, then oldest first.
+    pub fn oldest_pending(&self, limit: usize) -> Vec<&Task> {
+        let mut tasks = self.filter_by_done(false);
+        tasks.sort_by_key(|item| {
+            (
+                std::cmp::Reverse(item.snooze_count),
+                item.created_at,
+                item.id,
+            )
+        });
+        tasks.truncate(limit);
+        tasks
+    }
+
+    pub fn overdue_count(&self) -> usize {
+        let now = date_utils::now_secs();
+        self.tasks
+            .iter()
+            .filter(|item| item.is_overdue(now))
+            .count()
+    }
+
+    pub fn filter_by_tag(&self, tag: &str) -> Vec<&Task> {
+        self.tasks.iter().filter(|item| item.has_tag(tag)).collect()
+    }
+
+    pub fn filter_by_color(&self, label: &str) -> Vec<&Task> {
+        let label = label.trim().to_lowercase();
+        self.tasks
+            .iter()
+            .filter(|item| {
+                item.color_label
+                    .as_deref()
+                    .is_some_and(|color| color.to_lowercase() == label)
+            })
+            .collect()
+    }
+
+    pub fn export_csv(&self, path: &str) -> io::Result<()> {
+        let mut out = csv::write_record(&["id", "title", "description", "date", "done"]);
+        for item in &self.tasks {
+            out.push_str(&csv::write_record(&[
+                &item.id.to_string(),
+                &item.title,
+                &item.description,
+                &item.date,
+                &item.done.to_string(),
+            ]));
+        }
+        fs::write(path, out)
+    }
+
+    pub fn export_markdown(&self, path: &str) -> io::Result<()> {
+        let mut out = String::new();
+        for item in &self.tasks {
+            let checkbox = if item.done { "x" } else { " " };
+            out.push_str(&format!(
+                "- [{}] (id {}) {}",
+                checkbox,
+                item.id,
+                single_line(&item.title)
+            ));
+            let description = single_line(&item.description);
+            if !description.is_empty() {
+                out.push_str(&format!(" — {}", description));
+            }
+            if let Some(due) = &item.due_date {
+                out.push_str(&format!(" (due {})", due));
+            }
+            out.push('\n');
+        }
+        fs::write(path, out)
+    }
+
+    pub fn import_csv(&mut self, path: &str) -> Result<usize, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut imported = Vec::new();
+        for (line, fields) in csv::parse(&content)?.into_iter().skip(1) {
+            let [_, title, description, date, done] = fields.as_slice() else {
+                return Err(format!(
+                    "Malformed row on line {}: expected 5 fields, found {}.",
+                    line,
+                    fields.len()
+                ));
+            };
+            let done = done.parse::<bool>().map_err(|_| {
+                format!(
+                    "Malformed row on line {}: invalid done value '{}'.",
+                    line, done
+                )
+            })?;
+            // Same rules as adding by hand: blank titles are skipped, long fields rejected.
+            let title = title.trim();
+            if title.is_empty() {
+                continue;
+            }
+            check_length("Title", title, MAX_TITLE_LEN)
+                .and_then(|()| check_length("Description", description, MAX_DESCRIPTION_LEN))
+                .map_err(|e| format!("Line {}: {}", line, e))?;
+            imported.push((title.to_string(), description.clone(), date.clone(), done));
+        }
+
+        let count = imported.len();
+        self.check_capacity(count)?;
+        self.dirty |= count > 0;
+        let now = date_utils::now_secs();
+        for (title, description, date, done) in imported {
+            let mut task = Task::new(title, description, date, done);
+            // The CSV has no completion time, so finished rows count as completed on import.
+            task.completed_at = done.then_some(now);
+            self.tasks.push(task);
+        }
+        Ok(count)
+    }
+
+    /// Adds one task per non-blank line, using the trimmed line as the title.
+    pub fn import_lines(&mut self, path: &str) -> Result<usize, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        let mut titles = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let title = line.trim();
+            if title.is_empty() {
+                continue;
+            }
+            check_length("Title", title, MAX_TITLE_LEN)
+                .map_err(|e| format!("Line {}: {}", number + 1, e))?;
+            titles.push(title.to_string());
+        }
+
+        let count = titles.len();
+        self.check_capacity(count)?;
+        self.dirty |= count > 0;
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        for title in titles {
+            self.tasks
+                .push(Task::new(title, String::new(), date.clone(), false));
+        }
+        Ok(count)
+    }
+
+    pub fn delete_completed(&mut self) -> usize {
+        let (done, pending): (Vec<Task>, Vec<Task>) = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .partition(|item| item.done);
+        self.tasks = pending;
+        let count = done.len();
+        self.dirty |= count > 0;
+        self.archived.extend(done);
+        count
+    }
+
+    /// Archives every task carrying `tag` (case-insensitive) and returns how many moved.
+    pub fn delete_by_tag(&mut self, tag: &str) -> usize {
+        let (tagged, rest): (Vec<Task>, Vec<Task>) = std::mem::take(&mut self.tasks)
+            .into_iter()
+            .partition(|item| item.has_tag(tag));
+        self.tasks = rest;
+        let count = tagged.len();
+        self.dirty |= count > 0;
+        self.archived.extend(tagged);
+        count
+    }
+
+    pub fn archive_many(&mut self, ids: &[u32]) -> (usize, Vec<u32>) {
+        let mut missing = Vec::new();
+        let mut archived = 0;
+        for &id in ids {
+            match self.archive(id) {
+                Ok(_) => archived += 1,
+                Err(_) => missing.push(id),
+            }
+        }
+        (archived, missing)
+    }
+
+    /// Archives tasks completed before `cutoff` and returns how many moved.
+    pub fn archive_completed_before(&mut self, cutoff: u64) -> usize {
+        let ids: Vec<u32> = self
+            .tasks
+            .iter()
+            .filter(|item| item.done && item.completed_at.is_some_and(|secs| secs < cutoff))
+            .map(|item| item.id)
+            .collect();
+        self.archive_many(&ids).0
+    }
+
+    pub fn archive(&mut self, id: u32) -> Result<usize, String> {
+        let (index, task) = self.delete(id)?;
+        self.archived.push(task);
+        Ok(index)
+    }
+
+    pub fn restore(&mut self, id: u32) -> Result<(), String> {
+        self.restore_at(self.tasks.len(), id)
+    }
+
+    fn restore_at(&mut self, index: usize, id: u32) -> Result<(), String> {
+        let position = self
+            .archived
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| format!("Task with id {} is not in the archive.", id))?;
+        let task = self.archived.remove(position);
+        self.insert(index, task);
+        Ok(())
+    }
+
+    pub fn save_template(&mut self, name: &str, id: u32) -> Result<(), String> {
+        let task = self.get(id).ok_or_else(|| self.not_found(id))?;
+        let template = TaskTemplate::from_task(task);
+        self.templates.insert(name.to_string(), template);
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Adds a new task from the named template and returns its id.
+    pub fn add_from_template(&mut self, name: &str) -> Result<u32, String> {
+        self.check_capacity(1)?;
+        let task = self
+            .templates
+            .get(name)
+            .ok_or_else(|| format!("No template named '{}'.", name))?
+            .instantiate();
+        let id = task.id;
+        self.add(task);
+        Ok(id)
+    }
+
+    pub fn template_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.templates.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn get_archived(&self) -> &[Task] {
+        &self.archived
+    }
+
+    pub fn purge_archive(&mut self) -> usize {
+        self.dirty = true;
+        std::mem::take(&mut self.archived).len()
+    }
+
+    pub fn delete(&mut self, id: u32) -> Result<(usize, Task), String> {
+        if let Some(i) = self.tasks.iter().position(|item| item.id == id) {
+            self.dirty = true;
+            Ok((i, self.tasks.remove(i)))
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Task> {
+        self.tasks.iter().find(|item| item.id == id)
+    }
+
+    pub fn edit(
+        &mut self,
+        id: u32,
+        title: Option<String>,
+        description: Option<String>,
+    ) -> Result<(), String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            if let Some(title) = title {
+                item.title = title;
+            }
+            if let Some(description) = description {
+                item.description = description;
+            }
+            Ok(())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    pub fn duplicate(&mut self, id: u32) -> Result<u32, String> {
+        let source = self.get(id).ok_or_else(|| self.not_found(id))?;
+        self.check_capacity(1)?;
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        let mut task = Task::new(
+            source.title.clone(),
+            source.description.clone(),
+            date,
+            false,
+        );
+        task.due_date = source.due_date.clone();
+        task.tags = source.tags.clone();
+        task.priority = source.priority;
+        task.estimate_minutes = source.estimate_minutes;
+        task.color_label = source.color_label.clone();
+        task.link = source.link.clone();
+        let new_id = task.id;
+        self.add(task);
+        Ok(new_id)
+    }
+
+    pub fn set_due_date(&mut self, id: u32, due_date: Option<String>) -> Result<(), String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.due_date = due_date;
+            Ok(())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    /// Pushes the due date forward by `by`, counting from today when there is none.
+    pub fn snooze(&mut self, id: u32, by: Duration) -> Result<(), String> {
+        let index = self.index_of(id)?;
+        let item = &mut self.tasks[index];
+        let secs = by.as_secs();
+        let whole_days = secs.is_multiple_of(date_utils::SECS_PER_DAY);
+        item.due_date = Some(match item.due_at() {
+            Some(due) if item.has_due_time() || !whole_days => {
+                date_utils::format_datetime(due + secs)
+            }
+            Some(due) => date_utils::format_date(((due + secs) / date_utils::SECS_PER_DAY) as i64),
+            None if whole_days => date_utils::format_date(
+                date_utils::today() + (secs / date_utils::SECS_PER_DAY) as i64,
+            ),
+            None => date_utils::format_datetime(date_utils::now_secs() + secs),
+        });
+        item.snooze_count += 1;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Renumbers tasks 1..N in display order (pinned first, then `order`), then the
+    /// archive after them, keeping dependencies pointing at the same tasks.
+    pub fn compact_ids(&mut self, order: SortOrder) {
+        let old_ids: Vec<u32> = self
+            .display_order(order)
+            .iter()
+            .map(|item| item.id)
+            .chain(self.archived.iter().map(|item| item.id))
+            .collect();
+        let mapping: HashMap<u32, u32> = old_ids
+            .into_iter()
+            .enumerate()
+            .map(|(index, id)| (id, index as u32 + 1))
+            .collect();
+        for item in self.tasks.iter_mut().chain(&mut self.archived) {
+            item.id = mapping[&item.id];
+            item.blocked_by = item.blocked_by.and_then(|id| mapping.get(&id).copied());
+            item.next_occurrence_id = item
+                .next_occurrence_id
+                .and_then(|id| mapping.get(&id).copied());
+        }
+        self.dirty = true;
+    }
+
+    /// Flips the pinned flag and returns the new value.
+    pub fn toggle_pin(&mut self, id: u32) -> Result<bool, String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.pinned = !item.pinned;
+            Ok(item.pinned)
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    pub fn set_link(&mut self, id: u32, link: Option<String>) -> Result<(), String> {
+        if let Some(link) = &link {
+            check_link(link)?;
+        }
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.link = link;
+            Ok(())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    pub fn add_note(&mut self, id: u32, note: String) -> Result<(), String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            let stamp = date_utils::format_datetime(date_utils::now_secs());
+            item.notes.push(format!("[{}] {}", stamp, note));
+            Ok(())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    /// Adds `tag` to each listed task that lacks it and returns how many changed.
+    pub fn add_tag_to_many(&mut self, ids: &[u32], tag: &str) -> usize {
+        let tag = tag.trim();
+        let mut affected = 0;
+        for item in self.tasks.iter_mut().filter(|item| ids.contains(&item.id)) {
+            if !item.has_tag(tag) {
+                item.tags.push(tag.to_string());
+                affected += 1;
+            }
+        }
+        self.dirty |= affected > 0;
+        affected
+    }
+
+    pub fn remove_tag_from_many(&mut self, ids: &[u32], tag: &str) -> usize {
+        let mut affected = 0;
+        for item in self.tasks.iter_mut().filter(|item| ids.contains(&item.id)) {
+            if item.has_tag(tag) {
+                let tag = tag.trim().to_lowercase();
+                item.tags.retain(|existing| existing.to_lowercase() != tag);
+                affected += 1;
+            }
+        }
+        self.dirty |= affected > 0;
+        affected
+    }
+
+    /// Appends a subtask and returns its 1-based number.
+    pub fn add_subtask(&mut self, id: u32, title: String) -> Result<usize, String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.subtasks.push((title, false));
+            Ok(item.subtasks.len())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    /// Flips subtask `number` (1-based) and returns whether all subtasks are now done.
+    pub fn toggle_subtask(&mut self, id: u32, number: usize) -> Result<bool, String> {
+        let index = self.index_of(id)?;
+        let item = &mut self.tasks[index];
+        let Some((_, done)) = number.checked_sub(1).and_then(|i| item.subtasks.get_mut(i)) else {
+            return Err(format!("Task {} has no subtask #{}.", id, number));
+        };
+        *done = !*done;
+        self.dirty = true;
+        Ok(item.subtasks.iter().all(|(_, done)| *done))
+    }
+
+    pub fn set_priority(&mut self, id: u32, priority: Priority) -> Result<(), String> {
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.priority = priority;
+            Ok(())
+        } else {
+            Err(self.not_found(id))
+        }
+    }
+
+    pub fn set_dependency(&mut self, id: u32, blocker: Option<u32>) -> Result<(), String> {
+        if self.get(id).is_none() {
+            return Err(self.not_found(id));
+        }
+        if let Some(blocker) = blocker {
+            if blocker == id {
+                return Err("A task cannot block itself.".to_string());
+            }
+            let mut current = self.get(blocker).ok_or_else(|| self.not_found(blocker))?;
+            let mut seen = HashSet::new();
+            while let Some(next) = current.blocked_by {
+                if next == id {
+                    return Err(format!(
+                        "Task {} already depends on task {}; that would create a cycle.",
+                        blocker, id
+                    ));
+                }
+                if !seen.insert(next) {
+                    break;
+                }
+                match self.get(next) {
+                    Some(task) => current = task,
+                    None => break,
+                }
+            }
+        }
+        if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+            self.dirty = true;
+            item.blocked_by = blocker;
+        }
+        Ok(())
+    }
+
+    fn set_done(&mut self, id: u32, done: bool) -> Result<(), String> {
+        if done
+            && let Some(item) = self.get(id)
+            && let Some(blocker) = open_blocker(item, &self.tasks)
+        {
+            return Err(format!(
+                "Task {} is blocked by task {} ({}); finish that first.",
+                id,
+                blocker.id,
+                blocker.title.trim()
+            ));
+        }
+        let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) else {
+            return Err(self.not_found(id));
+        };
+        self.dirty = true;
+        let completed = done && !item.done;
+        item.done = done;
+        if completed {
+            item.completed_at = Some(date_utils::now_secs());
+        } else if !done {
+            item.completed_at = None;
+        }
+        // Re-completing after an undo must not queue a second copy of the next occurrence.
+        let already_queued = item.next_occurrence_id.is_some_and(|next| {
+            self.tasks
+                .iter()
+                .chain(&self.archived)
+                .any(|other| other.id == next)
+        });
+        if completed && !already_queued {
+            self.check_capacity(1).map_err(|e| {
+                format!(
+                    "Task {} is done, but its next occurrence was not created: {}",
+                    id, e
+                )
+            })?;
+        }
+        if completed
+            && !already_queued
+            && let Some(next) = self.get(id).and_then(Task::next_occurrence)
+        {
+            let next_id = next.id;
+            self.tasks.push(next);
+            if let Some(item) = self.tasks.iter_mut().find(|item| item.id == id) {
+                item.next_occurrence_id = Some(next_id);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mark_done(&mut self, id: u32) -> Result<(), String> {
+        self.set_done(id, true)
+    }
+
+    pub fn mark_not_done(&mut self, id: u32) -> Result<(), String> {
+        self.set_done(id, false)
+    }
+
+    pub fn toggle(&mut self, id: u32) -> Result<(), String> {
+        let done = self
+            .get(id)
+            .map(|item| item.done)
+            .ok_or_else(|| self.not_found(id))?;
+        self.set_done(id, !done)
+    }
+
+    pub fn move_up(&mut self, id: u32) -> Result<(), String> {
+        let index = self.index_of(id)?;
+        if index == 0 {
+            return Err(format!("Task {} is already at the top.", id));
+        }
+        self.dirty = true;
+        self.tasks.swap(index, index - 1);
+        Ok(())
+    }
+
+    pub fn move_down(&mut self, id: u32) -> Result<(), String> {
+        let index = self.index_of(id)?;
+        if index + 1 == self.tasks.len() {
+            return Err(format!("Task {} is already at the bottom.", id));
+        }
+        self.dirty = true;
+        self.tasks.swap(index, index + 1);
+        Ok(())
+    }
+
+    fn not_found(&self, id: u32) -> String {
+        let nearest = self
+            .tasks
+            .iter()
+            .map(|item| item.id)
+            .min_by_key(|&candidate| (candidate.abs_diff(id), candidate));
+        match nearest {
+            Some(nearest) => format!(
+                "Task with id {} not found. Did you mean id {}?",
+                id, nearest
+            ),
+            None => format!("Task with id {} not found. No tasks exist.", id),
+        }
+    }
+
+    fn missing_ids_result(&self, missing: &[u32]) -> Result<(), String> {
+        match missing {
+            [] => return Ok(()),
+            [id] => return Err(self.not_found(*id)),
+            _ => {}
+        }
+        let ids: Vec<String> = missing.iter().map(u32::to_string).collect();
+        Err(format!("Tasks not found: {}.", ids.join(", ")))
+    }
+
+    fn index_of(&self, id: u32) -> Result<usize, String> {
+        self.tasks
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or_else(|| self.not_found(id))
+    }
+}
+
+struct ListManager {
+    lists: BTreeMap<String, TasksModel>,
+    active: String,
+    max_tasks: usize,
+}
+impl ListManager {
+    /// Loads the default list and every list under `lists/`, activating `active`
+    /// (which is created empty if it doesn't exist yet).
+    fn load(active: &str) -> Result<Self, String> {
+        let mut manager = Self::with_model(DEFAULT_LIST, TasksModel::load_from(data_file_path()));
+        if let Ok(entries) = fs::read_dir(data_dir().join(LISTS_DIR)) {
+            for path in entries.flatten().map(|entry| entry.path()) {
+                let name = path.file_stem().and_then(|stem| stem.to_str());
+                if let Some(name) =
+                    name.filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+                {
+                    let name = name.to_string();
+                    manager.lists.insert(name, TasksModel::load_from(&path));
+                }
+            }
+        }
+        if !manager.lists.contains_key(active) {
+            manager.create(active)?;
+        }
+        manager.active = active.to_string();
+        Ok(manager)
+    }
+
+    fn with_model(name: &str, model: TasksModel) -> Self {
+        let mut lists = BTreeMap::new();
+        lists.insert(name.to_string(), model);
+        Self {
+            lists,
+            active: name.to_string(),
+            max_tasks: 0,
+        }
+    }
+
+    /// Applies the `max_tasks` limit to every list, including ones created later.
+    pub fn set_max_tasks(&mut self, max_tasks: usize) {
+        self.max_tasks = max_tasks;
+        for model in self.lists.values_mut() {
+            model.max_tasks = max_tasks;
+        }
+    }
+
+    fn list_path(name: &str) -> PathBuf {
+        if name == DEFAULT_LIST {
+            return data_file_path();
+        }
+        let dir = data_dir().join(LISTS_DIR);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("Warning: could not create {}: {}", dir.display(), e);
+        }
+        dir.join(format!("{}.json", name))
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), String> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(format!(
+                "Invalid list name '{}'; use letters, digits, '-' or '_'.",
+                name
+            ));
+        }
+        if self.lists.contains_key(name) {
+            return Err(format!("A list named '{}' already exists.", name));
+        }
+        Ok(())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.lists.keys().map(String::as_str).collect()
+    }
+
+    pub fn active_name(&self) -> &str {
+        &self.active
+    }
+
+    pub fn active(&self) -> &TasksModel {
+        &self.lists[&self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut TasksModel {
+        self.lists
+            .get_mut(&self.active)
+            .expect("active list is always loaded")
+    }
+
+    pub fn switch(&mut self, name: &str) -> Result<(), String> {
+        if !self.lists.contains_key(name) {
+            return Err(format!("No list named '{}'.", name));
+        }
+        self.active = name.to_string();
+        Ok(())
+    }
+
+    pub fn create(&mut self, name: &str) -> Result<(), String> {
+        self.check_name(name)?;
+        let mut model = TasksModel::load_from(Self::list_path(name));
+        // Written on the next flush so an empty list survives a restart.
+        model.dirty = true;
+        model.max_tasks = self.max_tasks;
+        self.lists.insert(name.to_string(), model);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), String> {
+        if name == DEFAULT_LIST {
+            return Err("The default list cannot be deleted.".to_string());
+        }
+        if name == self.active {
+            return Err("Switch to another list before deleting this one.".to_string());
+        }
+        let model = self
+            .lists
+            .remove(name)
+            .ok_or_else(|| format!("No list named '{}'.", name))?;
+        match fs::remove_file(&model.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(format!("Failed to delete {}: {}", model.path.display(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<(), String> {
+        if old == DEFAULT_LIST {
+            return Err("The default list cannot be renamed.".to_string());
+        }
+        self.check_name(new)?;
+        let mut model = self
+            .lists
+            .remove(old)
+            .ok_or_else(|| format!("No list named '{}'.", old))?;
+        let path = Self::list_path(new);
+        if model.path.exists()
+            && let Err(e) = fs::rename(&model.path, &path)
+        {
+            self.lists.insert(old.to_string(), model);
+            return Err(format!("Failed to rename list: {}", e));
+        }
+        model.path = path;
+        self.lists.insert(new.to_string(), model);
+        if self.active == old {
+            self.active = new.to_string();
+        }
+        Ok(())
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.lists.values().any(TasksModel::is_dirty)
+    }
+
+    /// Points the id counter just past the highest id in any list.
+    pub fn sync_id_counter(&self) {
+        id_generation::reset(1);
+        for model in self.lists.values() {
+            for item in model.get_all().iter().chain(model.get_archived()) {
+                id_generation::set_floor(item.id + 1);
+            }
+        }
+    }
+
+    /// Archives tasks finished more than `days` days before `now` in every list.
+    /// Returns how many were archived; 0 days turns the policy off.
+    pub fn auto_archive(&mut self, days: u64, now: u64) -> usize {
+        if days == 0 {
+            return 0;
+        }
+        let cutoff = now.saturating_sub(days * date_utils::SECS_PER_DAY);
+        self.lists
+            .values_mut()
+            .map(|model| model.archive_completed_before(cutoff))
+            .sum()
+    }
+
+    /// Records the end of a session in every list.
+    pub fn touch_opened(&mut self, secs: u64) {
+        for model in self.lists.values_mut() {
+            model.set_last_opened(secs);
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for model in self.lists.values_mut() {
+            model.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes every list with unsaved changes to `<dir>/<name>.json` and returns
+    /// their names. Saved lists count as clean until they change again.
+    pub fn save_copies(&mut self, dir: &Path) -> io::Result<Vec<String>> {
+        let mut saved = Vec::new();
+        for (name, model) in self.lists.iter_mut().filter(|(_, model)| model.is_dirty()) {
+            model.save_to(&dir.join(format!("{}.json", name)))?;
+            model.dirty = false;
+            saved.push(name.clone());
+        }
+        Ok(saved)
+    }
+}
+
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum MenuAction {
+    ShowTasks,
+    AddTask,
+    DeleteTask,
+    ToggleStatus,
+    ClearAll,
+    EditTask,
+    SetPriority,
+    FilterByStatus,
+    Search,
+    Undo,
+    ChangeSort,
+    FilterByTag,
+    ExportCsv,
+    ImportCsv,
+    MarkDone,
+    MarkNotDone,
+    DeleteCompleted,
+    ExportMarkdown,
+    Duplicate,
+    Today,
+    ExportJson,
+    ReopenLast,
+    MoveUp,
+    MoveDown,
+    ViewArchive,
+    RestoreArchived,
+    PurgeArchive,
+    AddNote,
+    SetDependency,
+    WeeklyReport,
+    TaskMenu,
+    FilterByColor,
+    SwitchList,
+    ManageLists,
+    QuickAdd,
+    SaveTemplate,
+    NewFromTemplate,
+    DoneSinceLastRun,
+    AddSubtask,
+    ToggleSubtask,
+    RepeatLastAdd,
+    SetAutoSort,
+    BulkTag,
+    Snooze,
+    ImportText,
+    OpenLink,
+    Focus,
+    DeleteByTag,
+    TogglePin,
+    CompactIds,
+    DueBetween,
+    Procrastinated,
+}
+impl MenuAction {
+    const ALL: [MenuAction; 52] = [
+        MenuAction::ShowTasks,
+        MenuAction::AddTask,
+        MenuAction::DeleteTask,
+        MenuAction::ToggleStatus,
+        MenuAction::ClearAll,
+        MenuAction::EditTask,
+        MenuAction::SetPriority,
+        MenuAction::FilterByStatus,
+        MenuAction::Search,
+        MenuAction::Undo,
+        MenuAction::ChangeSort,
+        MenuAction::FilterByTag,
+        MenuAction::ExportCsv,
+        MenuAction::ImportCsv,
+        MenuAction::MarkDone,
+        MenuAction::MarkNotDone,
+        MenuAction::DeleteCompleted,
+        MenuAction::ExportMarkdown,
+        MenuAction::Duplicate,
+        MenuAction::Today,
+        MenuAction::ExportJson,
+        MenuAction::ReopenLast,
+        MenuAction::MoveUp,
+        MenuAction::MoveDown,
+        MenuAction::ViewArchive,
+        MenuAction::RestoreArchived,
+        MenuAction::PurgeArchive,
+        MenuAction::AddNote,
+        MenuAction::SetDependency,
+        MenuAction::WeeklyReport,
+        MenuAction::TaskMenu,
+        MenuAction::FilterByColor,
+        MenuAction::SwitchList,
+        MenuAction::ManageLists,
+        MenuAction::QuickAdd,
+        MenuAction::SaveTemplate,
+        MenuAction::NewFromTemplate,
+        MenuAction::DoneSinceLastRun,
+        MenuAction::AddSubtask,
+        MenuAction::ToggleSubtask,
+        MenuAction::RepeatLastAdd,
+        MenuAction::SetAutoSort,
+        MenuAction::BulkTag,
+        MenuAction::Snooze,
+        MenuAction::ImportText,
+        MenuAction::OpenLink,
+        MenuAction::Focus,
+        MenuAction::DeleteByTag,
+        MenuAction::TogglePin,
+        MenuAction::CompactIds,
+        MenuAction::DueBetween,
+        MenuAction::Procrastinated,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            MenuAction::ShowTasks => "show_tasks",
+            MenuAction::AddTask => "add_task",
+            MenuAction::DeleteTask => "delete_task",
+            MenuAction::ToggleStatus => "toggle_status",
+            MenuAction::ClearAll => "clear_all",
+            MenuAction::EditTask => "edit_task",
+            MenuAction::SetPriority => "set_priority",
+            MenuAction::FilterByStatus => "filter_by_status",
+            MenuAction::Search => "search",
+            MenuAction::Undo => "undo",
+            MenuAction::ChangeSort => "change_sort",
+            MenuAction::FilterByTag => "filter_by_tag",
+            MenuAction::ExportCsv => "export_csv",
+            MenuAction::ImportCsv => "import_csv",
+            MenuAction::MarkDone => "mark_done",
+            MenuAction::MarkNotDone => "mark_not_done",
+            MenuAction::DeleteCompleted => "delete_completed",
+            MenuAction::ExportMarkdown => "export_markdown",
+            MenuAction::Duplicate => "duplicate",
+            MenuAction::Today => "today",
+            MenuAction::ExportJson => "export_json",
+            MenuAction::ReopenLast => "reopen_last",
+            MenuAction::MoveUp => "move_up",
+            MenuAction::MoveDown => "move_down",
+            MenuAction::ViewArchive => "view_archive",
+            MenuAction::RestoreArchived => "restore_archived",
+            MenuAction::PurgeArchive => "purge_archive",
+            MenuAction::AddNote => "add_note",
+            MenuAction::SetDependency => "set_dependency",
+            MenuAction::WeeklyReport => "weekly_report",
+            MenuAction::TaskMenu => "task_menu",
+            MenuAction::FilterByColor => "filter_by_color",
+            MenuAction::SwitchList => "switch_list",
+            MenuAction::ManageLists => "manage_lists",
+            MenuAction::QuickAdd => "quick_add",
+            MenuAction::SaveTemplate => "save_template",
+            MenuAction::NewFromTemplate => "new_from_template",
+            MenuAction::DoneSinceLastRun => "done_since_last_run",
+            MenuAction::AddSubtask => "add_subtask",
+            MenuAction::ToggleSubtask => "toggle_subtask",
+            MenuAction::RepeatLastAdd => "repeat_last_add",
+            MenuAction::SetAutoSort => "set_auto_sort",
+            MenuAction::BulkTag => "bulk_tag",
+            MenuAction::Snooze => "snooze",
+            MenuAction::ImportText => "import_text",
+            MenuAction::OpenLink => "open_link",
+            MenuAction::Focus => "focus",
+            MenuAction::DeleteByTag => "delete_by_tag",
+            MenuAction::TogglePin => "toggle_pin",
+            MenuAction::CompactIds => "compact_ids",
+            MenuAction::DueBetween => "due_between",
+            MenuAction::Procrastinated => "procrastinated",
+        }
+    }
+
+    fn shortcut(&self) -> Option<char> {
+        match self {
+            MenuAction::ShowTasks => Some('l'),
+            MenuAction::AddTask => Some('a'),
+            MenuAction::DeleteTask => Some('d'),
+            MenuAction::ToggleStatus => Some('t'),
+            _ => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            MenuAction::ShowTasks => "Show all tasks",
+            MenuAction::AddTask => "Add a task",
+            MenuAction::DeleteTask => "Delete a task",
+            MenuAction::ToggleStatus => "Toggle task status",
+            MenuAction::ClearAll => "Clear all",
+            MenuAction::EditTask => "Edit a task",
+            MenuAction::SetPriority => "Set task priority",
+            MenuAction::FilterByStatus => "Filter tasks by status",
+            MenuAction::Search => "Search tasks",
+            MenuAction::Undo => "Undo last action",
+            MenuAction::ChangeSort => "Change sort order",
+            MenuAction::FilterByTag => "Filter tasks by tag",
+            MenuAction::ExportCsv => "Export to CSV",
+            MenuAction::ImportCsv => "Import from CSV",
+            MenuAction::MarkDone => "Mark task done",
+            MenuAction::MarkNotDone => "Mark task not done",
+            MenuAction::DeleteCompleted => "Delete completed tasks",
+            MenuAction::ExportMarkdown => "Export to Markdown",
+            MenuAction::Duplicate => "Duplicate task",
+            MenuAction::Today => "Today's tasks",
+            MenuAction::ExportJson => "Export to JSON",
+            MenuAction::ReopenLast => "Reopen last completed",
+            MenuAction::MoveUp => "Move task up",
+            MenuAction::MoveDown => "Move task down",
+            MenuAction::ViewArchive => "View archive",
+            MenuAction::RestoreArchived => "Restore from archive",
+            MenuAction::PurgeArchive => "Permanently delete archive",
+            MenuAction::AddNote => "Add note to task",
+            MenuAction::SetDependency => "Set dependency",
+            MenuAction::WeeklyReport => "Weekly report",
+            MenuAction::TaskMenu => "Work on a task",
+            MenuAction::FilterByColor => "Filter tasks by color",
+            MenuAction::SwitchList => "Switch list",
+            MenuAction::ManageLists => "Create, delete or rename lists",
+            MenuAction::QuickAdd => "Quick add",
+            MenuAction::SaveTemplate => "Save task as template",
+            MenuAction::NewFromTemplate => "New task from template",
+            MenuAction::DoneSinceLastRun => "Done since last run",
+            MenuAction::AddSubtask => "Add subtask",
+            MenuAction::ToggleSubtask => "Toggle subtask",
+            MenuAction::RepeatLastAdd => "Repeat last add",
+            MenuAction::SetAutoSort => "Set default sort (saved)",
+            MenuAction::BulkTag => "Tag search results",
+            MenuAction::Snooze => "Snooze task",
+            MenuAction::ImportText => "Import from text",
+            MenuAction::OpenLink => "Open link",
+            MenuAction::Focus => "Focus on the next task",
+            MenuAction::DeleteByTag => "Delete by tag",
+            MenuAction::TogglePin => "Pin/unpin task",
+            MenuAction::CompactIds => "Compact task ids",
+            MenuAction::DueBetween => "Tasks due in a date range",
+            MenuAction::Procrastinated => "Most procrastinated",
+        }
+    }
+}
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A piece of a task that `display_tasks` can show.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DisplayField {
+    Id,
+    Status,
+    Priority,
+    Title,
+    Description,
+    Tags,
+    Due,
+    Created,
+}
+impl DisplayField {
+    const DEFAULT: [DisplayField; 8] = [
+        DisplayField::Id,
+        DisplayField::Status,
+        DisplayField::Priority,
+        DisplayField::Title,
+        DisplayField::Description,
+        DisplayField::Tags,
+        DisplayField::Due,
+        DisplayField::Created,
+    ];
+
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "id" => Some(DisplayField::Id),
+            "status" => Some(DisplayField::Status),
+            "priority" => Some(DisplayField::Priority),
+            "title" => Some(DisplayField::Title),
+            "description" => Some(DisplayField::Description),
+            "tags" => Some(DisplayField::Tags),
+            "due" => Some(DisplayField::Due),
+            "created" => Some(DisplayField::Created),
+            _ => None,
+        }
+    }
+}
+
+struct Config {
+    hidden_menu_items: HashSet<MenuAction>,
+    auto_sort: SortOrder,
+    date_format: String,
+    autosave: bool,
+    display_fields: Vec<DisplayField>,
+    confirm_clear_threshold: usize,
+    max_tasks: usize,
+    auto_archive_done_after_days: u64,
+    ascii_only: Option<bool>,
+    due_soon_days: u64,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            hidden_menu_items: HashSet::new(),
+            auto_sort: SortOrder::Id,
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            autosave: true,
+            display_fields: DisplayField::DEFAULT.to_vec(),
+            confirm_clear_threshold: 1,
+            max_tasks: 0,
+            auto_archive_done_after_days: 0,
+            ascii_only: None,
+            due_soon_days: 2,
+        }
+    }
+}
+impl Config {
+    fn load() -> Config {
+        let path = data_dir().join(CONFIG_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn parse(content: &str) -> Config {
+        let mut config = Self::default();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                eprintln!("Warning: ignoring config line {}: {}", number + 1, line);
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim().trim_matches('"'));
+            if let Err(e) = config.set(key, value) {
+                eprintln!("Warning: config line {}: {}", number + 1, e);
+            }
+        }
+        config
+    }
+
+    /// Saves one setting to the config file, keeping every other line as written.
+    fn store(key: &str, value: &str) -> io::Result<()> {
+        let path = data_dir().join(CONFIG_FILE);
+        let content = fs::read_to_string(&path).unwrap_or_default();
+        fs::create_dir_all(data_dir())?;
+        fs::write(&path, Self::with_setting(&content, key, value))
+    }
+
+    fn with_setting(content: &str, key: &str, value: &str) -> String {
+        let entry = format!("{} = \"{}\"", key, value);
+        let mut replaced = false;
+        let mut lines: Vec<String> = content
+            .lines()
+            .map(|line| {
+                let matches = line
+                    .split_once('=')
+                    .is_some_and(|(name, _)| name.trim() == key);
+                if matches && !replaced {
+                    replaced = true;
+                    entry.clone()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect();
+        if !replaced {
+            lines.push(entry);
+        }
+        lines.join("\n") + "\n"
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "auto_sort" | "default_sort" => {
+                self.auto_sort = SortOrder::parse(value)
+                    .ok_or_else(|| format!("unknown sort order '{}'", value))?;
+                return Ok(());
+            }
+            "ascii_only" => {
+                let ascii = value
+                    .parse::<bool>()
+                    .map_err(|_| format!("expected true or false for '{}'", key))?;
+                self.ascii_only = Some(ascii);
+                return Ok(());
+            }
+            "due_soon_days" => {
+                self.due_soon_days = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("expected a number for '{}'", key))?;
+                return Ok(());
+            }
+            "auto_archive_done_after_days" => {
+                self.auto_archive_done_after_days = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("expected a number for '{}'", key))?;
+                return Ok(());
+            }
+            "max_tasks" => {
+                self.max_tasks = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("expected a number for '{}'", key))?;
+                return Ok(());
+            }
+            "confirm_clear_threshold" => {
+                self.confirm_clear_threshold = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("expected a number for '{}'", key))?;
+                return Ok(());
+            }
+            "autosave" => {
+                self.autosave = value
+                    .parse::<bool>()
+                    .map_err(|_| format!("expected true or false for '{}'", key))?;
+                return Ok(());
+            }
+            "display_fields" => {
+                let mut fields = Vec::new();
+                let names = value.trim_matches(|c| c == '[' || c == ']').split(',');
+                for name in names.map(|name| name.trim().trim_matches('"')) {
+                    match DisplayField::parse(name) {
+                        Some(field) if !fields.contains(&field) => fields.push(field),
+                        Some(_) => {}
+                        None => eprintln!("Warning: ignoring unknown display field '{}'", name),
+                    }
+                }
+                if fields.is_empty() {
+                    return Err(
+                        "display_fields lists no known fields, using the default".to_string()
+                    );
+                }
+                self.display_fields = fields;
+                return Ok(());
+            }
+            "date_format" => {
+                if !date_utils::is_valid_format(value) {
+                    return Err(format!(
+                        "invalid date_format '{}', using {}",
+                        value, DEFAULT_DATE_FORMAT
+                    ));
+                }
+                self.date_format = value.to_string();
+                return Ok(());
+            }
+            _ => {}
+        }
+        let item = key
+            .strip_prefix("show_")
+            .and_then(|name| MenuAction::ALL.into_iter().find(|item| item.key() == name))
+            .ok_or_else(|| format!("unknown setting '{}'", key))?;
+        let shown = value
+            .parse::<bool>()
+            .map_err(|_| format!("expected true or false for '{}'", key))?;
+        if shown {
+            self.hidden_menu_items.remove(&item);
+        } else {
+            self.hidden_menu_items.insert(item);
+        }
+        Ok(())
+    }
+
+    fn menu_items(&self) -> Vec<MenuAction> {
+        MenuAction::ALL
+            .into_iter()
+            .filter(|item| !self.hidden_menu_items.contains(item))
+            .collect()
+    }
+}
+
+const DEFAULT_PAGE_SIZE: usize = 10;
+const ID_INPUT_RETRIES: usize = 3;
+const PROGRESS_BAR_WIDTH: usize = 10;
+
+trait InputOutput {
+    fn read_line(&mut self) -> Option<String>;
+    fn write_line(&mut self, line: &str);
+}
+
+struct StdIo;
+impl InputOutput for StdIo {
+    fn read_line(&mut self) -> Option<String> {
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(input),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// The symbols the view decorates tasks with, in emoji and plain-ASCII flavours.
+struct Glyphs {
+    done: &'static str,
+    not_done: &'static str,
+    blocked: &'static str,
+    pinned: &'static str,
+    high: &'static str,
+    color_dot: &'static str,
+    estimate: &'static str,
+    due: &'static str,
+    description: &'static str,
+    tags: &'static str,
+    link: &'static str,
+    note: &'static str,
+    overdue: &'static str,
+    soon: &'static str,
+    warning: &'static str,
+}
+impl Glyphs {
+    const EMOJI: Glyphs = Glyphs {
+        done: "✓",
+        not_done: "✗",
+        blocked: "🔒",
+        pinned: "📌",
+        high: "🔴",
+        color_dot: "●",
+        estimate: "⏱",
+        due: "📅",
+        description: "📝",
+        tags: "🏷",
+        link: "🔗",
+        note: "💬",
+        overdue: "⏰",
+        soon: "🔜",
+        warning: "⚠",
+    };
+    const ASCII: Glyphs = Glyphs {
+        done: "[x]",
+        not_done: "[ ]",
+        blocked: "[-]",
+        pinned: "[pin]",
+        high: "(!)",
+        color_dot: "*",
+        estimate: "est.",
+        due: "",
+        description: "note:",
+        tags: "tags:",
+        link: "link:",
+        note: "-",
+        overdue: "!!",
+        soon: ">>",
+        warning: "!",
+    };
+
+    /// Falls back to ASCII when the locale doesn't promise UTF-8 (e.g. LANG=C).
+    fn detect() -> &'static Glyphs {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|key| env::var(key).ok().filter(|value| !value.is_empty()));
+        match locale {
+            Some(locale) if locale.to_lowercase().replace('-', "").contains("utf8") => {
+                &Glyphs::EMOJI
+            }
+            None if cfg!(windows) => &Glyphs::EMOJI,
+            _ => &Glyphs::ASCII,
+        }
+    }
+
+    /// Prefixes `text` with `glyph`, if the current set has one.
+    fn label(glyph: &str, text: &str) -> String {
+        if glyph.is_empty() {
+            text.to_string()
+        } else {
+            format!("{} {}", glyph, text)
+        }
+    }
+}
+
+struct CliView {
+    io: RefCell<Box<dyn InputOutput>>,
+    page_size: usize,
+    use_color: bool,
+    date_format: String,
+    display_fields: Vec<DisplayField>,
+    glyphs: &'static Glyphs,
+    due_soon_days: u64,
+}
+impl CliView {
+    fn new() -> Self {
+        let mut view = Self::with_io(Box::new(StdIo));
+        view.use_color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+        view.glyphs = Glyphs::detect();
+        view
+    }
+
+    fn apply_config(&mut self, config: &Config) {
+        self.date_format = config.date_format.clone();
+        self.display_fields = config.display_fields.clone();
+        self.due_soon_days = config.due_soon_days;
+        match config.ascii_only {
+            Some(true) => self.glyphs = &Glyphs::ASCII,
+            Some(false) => self.glyphs = &Glyphs::EMOJI,
+            None => {}
+        }
+    }
+
+    fn with_io(io: Box<dyn InputOutput>) -> Self {
+        Self {
+            io: RefCell::new(io),
+            page_size: DEFAULT_PAGE_SIZE,
+            use_color: false,
+            date_format: DEFAULT_DATE_FORMAT.to_string(),
+            display_fields: DisplayField::DEFAULT.to_vec(),
+            glyphs: &Glyphs::EMOJI,
+            due_soon_days: Config::default().due_soon_days,
+        }
+    }
+
+    fn write_line(&self, line: &str) {
+        self.io.borrow_mut().write_line(line);
+    }
+
+    pub fn show_message(&self, message: &str) {
+        self.write_line(message);
+    }
+
+    fn format_moment(&self, secs: u64, with_time: bool) -> String {
+        if with_time {
+            date_utils::format_with(secs, &format!("{} %H:%M", self.date_format))
+        } else {
+            date_utils::format_with(secs, &self.date_format)
+        }
+    }
+
+    fn colorize(&self, text: &str, color: &str) -> String {
+        if self.use_color {
+            ansi::paint(text, color)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn show_menu(&self, items: &[MenuAction], list_name: &str) {
+        self.write_line("");
+        self.write_line("******************************************");
+        self.write_line("*              TODO LIST                 *");
+        self.write_line("******************************************");
+        if list_name != DEFAULT_LIST {
+            self.write_line(&format!("List: {}", list_name));
+        }
+        for (i, item) in items.iter().enumerate() {
+            match item.shortcut() {
+                Some(key) => self.write_line(&format!("{}. ({}) {}", i + 1, key, item.label())),
+                None => self.write_line(&format!("{}. {}", i + 1, item.label())),
+            }
+        }
+        self.write_line("0. (q) Exit");
+        self.write_line("******************************************");
+        self.write_line("");
+    }
+
+    pub fn show_task_menu(&self, id: u32) {
+        self.write_line(&format!("Task {}:", id));
+        self.write_line("1. Change title");
+        self.write_line("2. Toggle status");
+        self.write_line("3. Add note");
+        self.write_line("4. Set due date");
+        self.write_line("5. Delete");
+        self.write_line("0. (b) Back");
+    }
+
+    pub fn display_tasks(&self, tasks: &[&Task], all: &[Task]) {
+        if tasks.is_empty() {
+            self.write_line("Todo list is empty.");
+            return;
+        }
+        self.write_line("Your tasks");
+        self.write_line("******************************************");
+        // Pinned tasks lead every listing; the stable sort keeps the caller's order otherwise.
+        let mut tasks = tasks.to_vec();
+        tasks.sort_by_key(|task| !task.pinned);
+        let page_size = if self.page_size == 0 {
+            tasks.len()
+        } else {
+            self.page_size
+        };
+        let pages = tasks.len().div_ceil(page_size);
+        for (page, chunk) in tasks.chunks(page_size).enumerate() {
+            for task in chunk {
+                self.display_task(task, open_blocker(task, all));
+            }
+            if page + 1 < pages {
+                let prompt = format!(
+                    "Page {}/{}. Press Enter for next page, q to stop",
+                    page + 1,
+                    pages
+                );
+                let answer = self.get_user_input(&prompt);
+                if matches!(answer.as_deref(), None | Some("q" | "Q")) {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn display_task(&self, task: &Task, blocker: Option<&Task>) {
+        let glyphs = self.glyphs;
+        let priority = effective_priority(task, date_utils::now_secs());
+        let (status, color) = match (task.done, task.completed_at) {
+            (true, Some(secs)) => (
+                format!("{} Done ({})", glyphs.done, self.format_moment(secs, true)),
+                ansi::GREEN,
+            ),
+            (true, None) => (format!("{} Done", glyphs.done), ansi::GREEN),
+            _ if blocker.is_some() => (format!("{} Blocked", glyphs.blocked), ansi::RED),
+            _ => (format!("{} Not done", glyphs.not_done), ansi::RED),
+        };
+        let now = date_utils::now_secs();
+        let overdue = task.is_overdue(now);
+        let due_soon = task.is_due_soon(now, self.due_soon_days * date_utils::SECS_PER_DAY);
+        // Overdue and due-soon headers are painted as a whole, so the status stays plain inside them.
+        let status = if overdue || due_soon {
+            status
+        } else {
+            self.colorize(&status, color)
+        };
+
+        let description = task.description.trim().replace('\n', "\n    ");
+        let mut due = match (&task.due_date, task.due_at()) {
+            (Some(_), Some(secs)) => {
+                format!("due: {}", self.format_moment(secs, task.has_due_time()))
+            }
+            (Some(due), None) => format!("due: {}", due),
+            (None, _) => "no due date".to_string(),
+        };
+        if let Some(recurrence) = task.recurrence {
+            due.push_str(&format!(" (repeats {})", recurrence.as_str()));
+        }
+        let mut marker = if task.pinned {
+            format!("{} ", glyphs.pinned)
+        } else {
+            String::new()
+        };
+        if priority == Priority::High {
+            marker.push_str(&format!("{} ", glyphs.high));
+        }
+        if let Some(label) = &task.color_label {
+            match ansi::named(label).filter(|_| self.use_color) {
+                Some(color) => {
+                    marker.push_str(&format!("{} ", ansi::paint(glyphs.color_dot, color)))
+                }
+                None => marker.push_str(&format!("{} {} ", glyphs.color_dot, label)),
+            }
+        }
+        if let Some(minutes) = task.estimate_minutes {
+            due.push_str(&format!(" | {} ~{}m", glyphs.estimate, minutes));
+        }
+        if task.snooze_count > 0 {
+            due.push_str(&format!(" | snoozed {}x", task.snooze_count));
+        }
+        let mut created = date_utils::display(&task.date);
+        if task.created_at > 0 {
+            created = self.format_moment(task.created_at, true);
+            let age = date_utils::relative_age(task.created_at, date_utils::now_secs());
+            created.push_str(&format!(" ({})", age));
+        }
+        let mut title = task.title.trim().to_string();
+        if !task.subtasks.is_empty() {
+            let (done, total) = task.subtask_progress();
+            title.push_str(&format!(" ({}/{})", done, total));
+        }
+        let blocked = blocker.map(|blocker| {
+            format!(
+                " {} blocked by id {} ({})",
+                glyphs.blocked,
+                blocker.id,
+                blocker.title.trim()
+            )
+        });
+
+        // Consecutive short fields share a line; description and tags get their own.
+        let mut lines: Vec<String> = Vec::new();
+        let mut inline: Vec<String> = Vec::new();
+        let mut blocked_shown = false;
+        for field in &self.display_fields {
+            let segment = match field {
+                DisplayField::Id => format!("id: {}", task.id),
+                DisplayField::Status => format!("status: {}", status),
+                DisplayField::Priority if priority != task.priority => {
+                    format!("priority: {} (due soon)", priority.as_str())
+                }
+                DisplayField::Priority => format!("priority: {}", priority.as_str()),
+                DisplayField::Title => format!("title: {}", title),
+                DisplayField::Due => Glyphs::label(glyphs.due, &due),
+                DisplayField::Created => format!("created: {}", created),
+                DisplayField::Description | DisplayField::Tags => {
+                    if !inline.is_empty() {
+                        lines.push(inline.join(" | "));
+                        inline.clear();
+                    }
+                    if *field == DisplayField::Description && !description.is_empty() {
+                        let description = format!("{:<40}", description);
+                        lines.push(format!(
+                            " {}",
+                            Glyphs::label(glyphs.description, &description)
+                        ));
+                    }
+                    if *field == DisplayField::Tags {
+                        if !task.tags.is_empty() {
+                            let tags = task.tags.join(", ");
+                            lines.push(format!(" {}", Glyphs::label(glyphs.tags, &tags)));
+                        }
+                        lines.extend(blocked.clone());
+                        blocked_shown = true;
+                    }
+                    continue;
+                }
+            };
+            inline.push(segment);
+        }
+        if !inline.is_empty() {
+            lines.push(inline.join(" | "));
+        }
+        if !blocked_shown {
+            lines.extend(blocked);
+        }
+        if let Some(link) = &task.link {
+            lines.push(format!(" {}", Glyphs::label(glyphs.link, link)));
+        }
+        for line in lines.iter_mut().skip(1) {
+            if !line.starts_with(' ') {
+                line.insert(0, ' ');
+            }
+        }
+
+        let mut header = format!("{}{}", marker, lines.first().map_or("", String::as_str));
+        if overdue {
+            header = self.colorize(&format!("{} OVERDUE {}", glyphs.overdue, header), ansi::RED);
+        } else if due_soon {
+            header = self.colorize(&format!("{} soon {}", glyphs.soon, header), ansi::YELLOW);
+        }
+        let mut output = header;
+        for line in lines.iter().skip(1) {
+            output.push('\n');
+            output.push_str(line);
+        }
+        output.push('\n');
+        output.extend(task.subtasks.iter().enumerate().map(|(i, (title, done))| {
+            let check = if *done { "x" } else { " " };
+            format!("    {}. [{}] {}\n", i + 1, check, title)
+        }));
+        output.extend(
+            task.notes
+                .iter()
+                .map(|note| format!("    {} {}\n", glyphs.note, note)),
+        );
+        self.write_line(&output);
+        self.write_line("******************************************");
+    }
+
+    /// One-line confirmation of a newly added task.
+    pub fn show_added(&self, task: &Task) {
+        let due = match task.due_at() {
+            Some(secs) => format!("due {}", self.format_moment(secs, task.has_due_time())),
+            None => "no due date".to_string(),
+        };
+        self.write_line(&format!(
+            "Added task #{}: '{}' ({}, priority {})",
+            task.id,
+            single_line(&task.title),
+            due,
+            task.priority.as_str()
+        ));
+    }
+
+    pub fn display_stats(
+        &self,
+        (total, done, pending): (usize, usize, usize),
+        overdue: usize,
+        workload: u32,
+    ) {
+        let overdue = if overdue > 0 {
+            self.colorize(&format!("Overdue: {}", overdue), ansi::RED)
+        } else {
+            "Overdue: 0".to_string()
+        };
+        let mut line = format!(
+            "Total: {} | Done: {} | Pending: {} | {}",
+            total, done, pending, overdue
+        );
+        if workload > 0 {
+            line.push_str(&format!(
+                " | Pending workload: {}",
+                date_utils::format_minutes(workload)
+            ));
+        }
+        self.write_line(&line);
+    }
+
+    pub fn display_progress(&self, percent: u8) {
+        let filled = (percent as usize * PROGRESS_BAR_WIDTH + 50) / 100;
+        self.write_line(&format!(
+            "[{}{}] {}%",
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_BAR_WIDTH - filled),
+            percent
+        ));
+    }
+
+    pub fn display_overdue_banner(&self, tasks: &[&Task]) {
+        if tasks.is_empty() {
+            return;
+        }
+        self.write_line(&format!(
+            "{} You have {} overdue tasks:",
+            self.glyphs.warning,
+            tasks.len()
+        ));
+        for task in tasks {
+            self.write_line(&format!("  id {}: {}", task.id, task.title.trim()));
+        }
+    }
+
+    fn read_line(&self) -> Option<String> {
+        let line = self.io.borrow_mut().read_line()?;
+        Some(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    pub fn get_user_input(&self, prompt: &str) -> Option<String> {
+        self.write_line(prompt);
+        self.read_line().map(|input| input.trim().to_string())
+    }
+
+    /// Opens `initial` in `$EDITOR` (or vi, then nano) and returns the saved text.
+    pub fn edit_in_external_editor(&self, initial: &str) -> io::Result<String> {
+        let (scratch, mut file) = ScratchFile::create("rust_todo_edit")?;
+        file.write_all(initial.as_bytes())?;
+        drop(file);
+        let path = &scratch.0;
+        let configured = env::var("EDITOR")
+            .ok()
+            .filter(|editor| !editor.trim().is_empty());
+        let candidates = match &configured {
+            Some(editor) => vec![editor.as_str()],
+            None => vec!["vi", "nano"],
+        };
+        let mut result = Err(io::Error::other("no editor available; set $EDITOR"));
+        for editor in candidates {
+            // Allow values like "code --wait".
+            let mut parts = editor.split_whitespace();
+            let Some(program) = parts.next() else {
+                continue;
+            };
+            match std::process::Command::new(program)
+                .args(parts)
+                .arg(path)
+                .status()
+            {
+                Ok(status) if status.success() => {
+                    result = fs::read_to_string(path);
+                    break;
+                }
+                Ok(status) => {
+                    result = Err(io::Error::other(format!(
+                        "{} exited with {}",
+                        program, status
+                    )));
+                    break;
+                }
+                Err(e) => {
+                    result = Err(io::Error::other(format!(
+                        "could not run {}: {}",
+                        program, e
+                    )))
+                }
+            }
+        }
+        result.map(|text| text.trim().to_string())
+    }
+
+    pub fn get_multiline_input(&self, prompt: &str) -> String {
+        self.read_multiline(prompt, None)
+    }
+
+    /// Like `get_multiline_input`, but returns the "!edit" sentinel as soon as it is
+    /// typed as the first line, so the editor opens without waiting for ".".
+    pub fn get_description_input(&self, prompt: &str) -> String {
+        self.read_multiline(prompt, Some(EDITOR_SENTINEL))
+    }
+
+    fn read_multiline(&self, prompt: &str, sentinel: Option<&str>) -> String {
+        self.write_line(&format!(
+            "{} (finish with a line containing only \".\")",
+            prompt
+        ));
+        let mut lines = Vec::new();
+        while let Some(line) = self.read_line() {
+            if line.trim() == "." {
+                break;
+            }
+            if lines.is_empty() && sentinel == Some(line.trim()) {
+                return line.trim().to_string();
+            }
+            lines.push(line);
+        }
+        lines.join("\n").trim().to_string()
+    }
+
+    /// Lists `tasks` by position and returns the id of the one picked. 0 or blank cancels.
+    pub fn pick_task(&self, tasks: &[Task]) -> Option<u32> {
+        if tasks.is_empty() {
+            self.show_message("No tasks to pick from.");
+            return None;
+        }
+        for (position, task) in tasks.iter().enumerate() {
+            let check = if task.done { "x" } else { " " };
+            self.write_line(&format!(
+                "{:>3}. [{}] {} (id {})",
+                position + 1,
+                check,
+                single_line(&task.title),
+                task.id
+            ));
+        }
+        let input = self.get_user_input("Pick a task number (0 or blank to cancel):")?;
+        match input.parse::<usize>() {
+            Ok(0) => None,
+            Ok(n) if n <= tasks.len() => Some(tasks[n - 1].id),
+            _ if input.is_empty() => None,
+            _ => {
+                self.show_message(&format!("No task at position '{}'.", input));
+                None
+            }
+        }
+    }
+
+    /// Like `get_user_input`, but blank keeps `default` and "-" clears it.
+    pub fn get_input_or(&self, prompt: &str, default: Option<&str>) -> String {
+        let Some(default) = default.filter(|default| !default.is_empty()) else {
+            return self.get_user_input(prompt).unwrap_or_default();
+        };
+        let input = self
+            .get_user_input(&format!("{} [{}]", prompt, default))
+            .unwrap_or_default();
+        match input.as_str() {
+            "" => default.to_string(),
+            "-" => String::new(),
+            _ => input,
+        }
+    }
+
+    /// Reads a numeric id, reprompting on invalid input. Blank input cancels.
+    pub fn get_id_input(&self, prompt: &str) -> Option<u32> {
+        let mut input = self.get_user_input(prompt)?;
+        for _ in 0..ID_INPUT_RETRIES {
+            if input.is_empty() {
+                return None;
+            }
+            if let Ok(id) = input.parse::<u32>() {
+                return Some(id);
+            }
+            input = self.get_user_input("Please enter a numeric id (or blank to cancel):")?;
+        }
+        let id = input.parse::<u32>().ok();
+        if id.is_none() && !input.is_empty() {
+            self.show_message("No valid id entered; cancelled.");
+        }
+        id
+    }
+
+    pub fn confirm(&self, prompt: &str) -> bool {
+        let answer = self.get_user_input(prompt).unwrap_or_default();
+        let answer = answer.to_lowercase();
+        answer == "y" || answer == "yes"
+    }
+}
+
+/// The priority a pending task is shown with: High when due within a day (or overdue),
+/// at least Medium within three days. The stored priority is never changed.
+fn effective_priority(task: &Task, now: u64) -> Priority {
+    let Some(due) = task.due_at().filter(|_| !task.done) else {
+        return task.priority;
+    };
+    let remaining = due.saturating_sub(now);
+    if remaining <= date_utils::SECS_PER_DAY {
+        Priority::High
+    } else if remaining <= 3 * date_utils::SECS_PER_DAY {
+        task.priority.max(Priority::Medium)
+    } else {
+        task.priority
+    }
+}
+
+/// Returns the task blocking `task`, if it exists and is still pending.
+fn open_blocker<'a>(task: &Task, tasks: &'a [Task]) -> Option<&'a Task> {
+    let id = task.blocked_by?;
+    tasks.iter().find(|item| item.id == id && !item.done)
+}
+
+fn single_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+const EDITOR_SENTINEL: &str = "!edit";
+
+/// A freshly created file in the temp directory, removed again when dropped.
+struct ScratchFile(PathBuf);
+impl ScratchFile {
+    /// Creates `<prefix>_<pid>_<salt>_<n>.txt` exclusively, so a file or symlink planted
+    /// at a guessable path is never opened; on a clash the next name is tried.
+    fn create(prefix: &str) -> io::Result<(ScratchFile, fs::File)> {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let salt = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_nanos());
+        let mut last_error = None;
+        for _ in 0..100 {
+            let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+            let name = format!("{}_{}_{:x}_{}.txt", prefix, std::process::id(), salt, n);
+            let path = env::temp_dir().join(name);
+            let mut options = fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            match options.open(&path) {
+                Ok(file) => return Ok((ScratchFile(path), file)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => last_error = Some(e),
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("no free temp file name")))
+    }
+}
+impl Drop for ScratchFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+const MAX_TITLE_LEN: usize = 120;
+const MAX_DESCRIPTION_LEN: usize = 500;
+
+/// Lengths are counted in chars so multibyte text isn't penalised.
+fn check_length(field: &str, text: &str, max: usize) -> Result<(), String> {
+    let len = text.chars().count();
+    if len > max {
+        return Err(format!("{} too long ({} chars, max {}).", field, len, max));
+    }
+    Ok(())
+}
+
+/// Accepts only http:// and https:// links without whitespace.
+fn check_link(link: &str) -> Result<(), String> {
+    let rest = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"));
+    match rest {
+        Some(rest) if !rest.is_empty() && !rest.contains(char::is_whitespace) => Ok(()),
+        _ => Err(format!(
+            "'{}' doesn't look like a link; it must start with http:// or https://.",
+            link
+        )),
+    }
+}
+
+/// The parts of a quick-add line, kept apart from `Task` so that a rejected
+/// title doesn't use up an id.
+struct QuickAdd {
+    title: String,
+    tags: Vec<String>,
+    priority: Priority,
+    due_date: Option<String>,
+}
+impl QuickAdd {
+    fn into_task(self) -> Task {
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        let mut task = Task::new(self.title, String::new(), date, false);
+        task.tags = self.tags;
+        task.priority = self.priority;
+        task.due_date = self.due_date;
+        task
+    }
+}
+
+/// Parses "Buy milk @home !high due:2024-06-12": `@` adds a tag, `!` sets the
+/// priority and `due:` the due date; every other token is part of the title.
+fn parse_quick_add(input: &str) -> QuickAdd {
+    let mut title = Vec::new();
+    let mut tags = Vec::new();
+    let mut priority = None;
+    let mut due_date = None;
+    for token in input.split_whitespace() {
+        if let Some(tag) = token.strip_prefix('@').filter(|tag| !tag.is_empty()) {
+            tags.push(tag.to_string());
+        } else if let Some(level) = token.strip_prefix('!').and_then(Priority::parse) {
+            priority = Some(level);
+        } else if let Some(due) = token
+            .strip_prefix("due:")
+            .filter(|due| date_utils::parse_due(due).is_some())
+        {
+            due_date = Some(due.to_string());
+        } else {
+            title.push(token);
+        }
+    }
+    QuickAdd {
+        title: title.join(" "),
+        tags,
+        priority: priority.unwrap_or_default(),
+        due_date,
+    }
+}
+
+fn normalize_for_search(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    text.to_lowercase()
+        .chars()
+        .filter(|c| !('\u{300}'..='\u{36f}').contains(c))
+        .map(strip_accent)
+        .collect()
+}
+
+fn strip_accent(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ğ' => 'g',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' | 'ı' => 'i',
+        'ł' | 'ľ' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ř' => 'r',
+        'ś' | 'š' | 'ş' => 's',
+        'ť' | 'ţ' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' | 'ų' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        _ => c,
+    }
+}
+
+fn parse_tags(input: &str) -> Vec<String> {
+    input
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_id_list(input: &str) -> Result<Vec<u32>, String> {
+    let parse = |text: &str| {
+        text.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid task id '{}'.", text.trim()))
+    };
+    let mut ids = Vec::new();
+    for part in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let (start, end) = (parse(start)?, parse(end)?);
+                if start > end {
+                    return Err(format!("Invalid range '{}'.", part));
+                }
+                ids.extend(start..=end);
+            }
+            None => ids.push(parse(part)?),
+        }
+    }
+    if ids.is_empty() {
+        return Err("No task ids given.".to_string());
+    }
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    Ok(ids)
+}
+
+const UNDO_LIMIT: usize = 20;
+const PROCRASTINATED_LIMIT: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Id,
+    DueDate,
+    Priority,
+    Created,
+    Manual,
+}
+impl SortOrder {
+    fn parse(input: &str) -> Option<Self> {
+        match input.trim().to_lowercase().as_str() {
+            "id" => Some(SortOrder::Id),
+            "due" | "due_date" => Some(SortOrder::DueDate),
+            "priority" => Some(SortOrder::Priority),
+            "created" => Some(SortOrder::Created),
+            "manual" | "none" => Some(SortOrder::Manual),
+            _ => None,
+        }
+    }
+
+    /// The value written to the config file.
+    fn key(&self) -> &'static str {
+        match self {
+            SortOrder::Id => "id",
+            SortOrder::DueDate => "due",
+            SortOrder::Priority => "priority",
+            SortOrder::Created => "created",
+            SortOrder::Manual => "none",
+        }
+    }
+
+    /// Manual order compares everything equal, so a stable sort keeps the stored order.
+    fn compare(&self, a: &Task, b: &Task) -> std::cmp::Ordering {
+        match self {
+            SortOrder::Id => a.id.cmp(&b.id),
+            SortOrder::DueDate => {
+                let key = |task: &Task| {
+                    let due = task.due_at();
+                    (due.is_none(), due, task.id)
+                };
+                key(a).cmp(&key(b))
+            }
+            SortOrder::Priority => {
+                let key = |task: &Task| (task.done, std::cmp::Reverse(task.priority), task.id);
+                key(a).cmp(&key(b))
+            }
+            SortOrder::Created => (a.created_at, a.id).cmp(&(b.created_at, b.id)),
+            SortOrder::Manual => std::cmp::Ordering::Equal,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Id => "id",
+            SortOrder::DueDate => "due date",
+            SortOrder::Priority => "priority",
+            SortOrder::Created => "creation time",
+            SortOrder::Manual => "manual order",
+        }
+    }
+}
+
+enum UndoAction {
+    Archive(Vec<(usize, u32)>),
+}
+
+struct Presenter {
+    lists: ListManager,
+    view: CliView,
+    history: Vec<UndoAction>,
+    sort_order: SortOrder,
+    last_completed: Option<u32>,
+    last_added: Option<Task>,
+    menu: Vec<MenuAction>,
+    autosave: bool,
+    confirm_clear_threshold: usize,
+}
+impl Presenter {
+    pub fn new(mut lists: ListManager, mut view: CliView, config: &Config) -> Self {
+        view.apply_config(config);
+        lists.set_max_tasks(config.max_tasks);
+        Self {
+            lists,
+            view,
+            history: Vec::new(),
+            sort_order: config.auto_sort,
+            last_completed: None,
+            last_added: None,
+            menu: config.menu_items(),
+            autosave: config.autosave,
+            confirm_clear_threshold: config.confirm_clear_threshold,
+        }
+    }
+
+    fn record(&mut self, action: UndoAction) {
+        if self.history.len() == UNDO_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push(action);
+    }
+
+    pub fn interaction_loop(&mut self) {
+        let save = loop {
+            self.view.show_menu(&self.menu, self.lists.active_name());
+
+            // End of input (Ctrl-D) leaves nobody to ask, so it always saves.
+            let Some(input) = self.view.get_user_input("Select an option:") else {
+                break true;
+            };
+            if input.eq_ignore_ascii_case("q") || input == "0" {
+                break self.confirm_exit();
+            }
+            let shortcut = self.menu.iter().copied().find(|item| {
+                item.shortcut()
+                    .is_some_and(|key| input.eq_ignore_ascii_case(&key.to_string()))
+            });
+            let result = match (input.parse::<usize>(), shortcut) {
+                (_, Some(action)) => self.run_action(action),
+                (Ok(n), _) if n >= 1 && n <= self.menu.len() => self.run_action(self.menu[n - 1]),
+                _ => Err("Invalid option, try again.".to_string()),
+            };
+            let result = if self.autosave {
+                result.and(self.flush())
+            } else {
+                result
+            };
+            if let Err(e) = result {
+                self.view.show_message(&e);
+            }
+        };
+        if !save {
+            return;
+        }
+        self.lists.touch_opened(date_utils::now_secs());
+        if let Err(e) = self.flush() {
+            self.view.show_message(&e);
+        }
+    }
+
+    /// Asks whether to save pending changes; returns false to discard them.
+    fn confirm_exit(&self) -> bool {
+        if !self.lists.is_dirty() {
+            return true;
+        }
+        loop {
+            let Some(input) = self
+                .view
+                .get_user_input("You have unsaved changes. Save before exiting? (Y/n):")
+            else {
+                return true;
+            };
+            match input.to_lowercase().as_str() {
+                "" | "y" | "yes" => return true,
+                "n" | "no" => return false,
+                _ => self.view.show_message("Please answer y or n."),
+            }
+        }
+    }
+
+    fn run_action(&mut self, action: MenuAction) -> Result<(), String> {
+        match action {
+            MenuAction::ShowTasks => self.show_tasks(),
+            MenuAction::AddTask => self.add_task(),
+            MenuAction::DeleteTask => self.delete_task(),
+            MenuAction::ToggleStatus => self.toggle_status(),
+            MenuAction::ClearAll => self.delete_tasks(),
+            MenuAction::EditTask => self.edit_task(),
+            MenuAction::SetPriority => self.set_priority(),
+            MenuAction::FilterByStatus => self.filter_tasks(),
+            MenuAction::Search => self.search_tasks(),
+            MenuAction::Undo => self.undo(),
+            MenuAction::ChangeSort => self.choose_sort_order(),
+            MenuAction::FilterByTag => self.filter_by_tag(),
+            MenuAction::ExportCsv => self.export_csv(),
+            MenuAction::ImportCsv => self.import_csv(),
+            MenuAction::MarkDone => self.mark_done(),
+            MenuAction::MarkNotDone => self.mark_not_done(),
+            MenuAction::DeleteCompleted => self.delete_completed(),
+            MenuAction::ExportMarkdown => self.export_markdown(),
+            MenuAction::Duplicate => self.duplicate_task(),
+            MenuAction::Today => self.show_today(),
+            MenuAction::ExportJson => self.export_json(),
+            MenuAction::ReopenLast => self.reopen_last_completed(),
+            MenuAction::MoveUp => self.move_task(TasksModel::move_up),
+            MenuAction::MoveDown => self.move_task(TasksModel::move_down),
+            MenuAction::ViewArchive => self.show_archive(),
+            MenuAction::RestoreArchived => self.restore_archived(),
+            MenuAction::PurgeArchive => self.purge_archive(),
+            MenuAction::AddNote => self.add_note(),
+            MenuAction::SetDependency => self.set_dependency(),
+            MenuAction::WeeklyReport => self.weekly_report(),
+            MenuAction::TaskMenu => self.choose_task(),
+            MenuAction::FilterByColor => self.filter_by_color(),
+            MenuAction::SwitchList => self.switch_list(),
+            MenuAction::ManageLists => self.manage_lists(),
+            MenuAction::QuickAdd => self.quick_add(),
+            MenuAction::SaveTemplate => self.save_template(),
+            MenuAction::NewFromTemplate => self.new_from_template(),
+            MenuAction::DoneSinceLastRun => self.done_since_last_run(),
+            MenuAction::AddSubtask => self.add_subtask(),
+            MenuAction::ToggleSubtask => self.toggle_subtask(),
+            MenuAction::RepeatLastAdd => self.repeat_last_add(),
+            MenuAction::SetAutoSort => self.set_auto_sort(),
+            MenuAction::BulkTag => self.bulk_tag(),
+            MenuAction::Snooze => self.snooze_task(),
+            MenuAction::ImportText => self.import_text(),
+            MenuAction::OpenLink => self.open_link(),
+            MenuAction::Focus => self.focus(),
+            MenuAction::DeleteByTag => self.delete_by_tag(),
+            MenuAction::TogglePin => self.toggle_pin(),
+            MenuAction::CompactIds => self.compact_ids(),
+            MenuAction::DueBetween => self.due_between(),
+            MenuAction::Procrastinated => self.show_procrastinated(),
+        }
+    }
+
+    /// Checks the task limit before prompting, so a full list fails fast.
+    fn check_capacity(&self) -> Result<(), String> {
+        self.lists.active().check_capacity(1)
+    }
+
+    /// Saves every list, offering a retry or an alternate path when writing fails.
+    /// Unsaved changes stay in memory either way.
+    fn flush(&mut self) -> Result<(), String> {
+        loop {
+            let Err(e) = self.lists.flush() else {
+                return Ok(());
+            };
+            let Some(answer) = self
+                .view
+                .get_user_input(&format!("Failed to save: {}. Retry? (Y/n)", e))
+            else {
+                return Err(format!("Failed to save tasks: {}", e));
+            };
+            if !matches!(answer.to_lowercase().as_str(), "" | "y" | "yes") {
+                break;
+            }
+        }
+        let dir = self
+            .view
+            .get_user_input(
+                "Save copies of the unsaved lists to another directory (blank to skip):",
+            )
+            .unwrap_or_default();
+        if dir.is_empty() {
+            return Err("Changes not saved; they are kept in memory for now.".to_string());
+        }
+        let saved = self
+            .lists
+            .save_copies(Path::new(&dir))
+            .map_err(|e| format!("Failed to save to {}: {}", dir, e))?;
+        self.view.show_message(&format!(
+            "Saved {} to {}. The usual files are written again once a list changes.",
+            saved.join(", "),
+            dir
+        ));
+        Ok(())
+    }
+
+    pub fn show_tasks(&mut self) -> Result<(), String> {
+        let tasks = match self.sort_order {
+            SortOrder::Id => self.lists.active().sorted_by_id(),
+            SortOrder::DueDate => self.lists.active().sorted_by_due(),
+            SortOrder::Priority => self.lists.active().sorted_by_priority(),
+            order => self.lists.active().sorted(order),
+        };
+        self.view
+            .display_progress(self.lists.active().completion_percent());
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        self.view.display_stats(
+            self.lists.active().stats(),
+            self.lists.active().overdue_count(),
+            self.lists.active().total_estimate(),
+        );
+        Ok(())
+    }
+
+    pub fn choose_sort_order(&mut self) -> Result<(), String> {
+        let input = self
+            .view
+            .get_user_input("Sort by: (1) id (2) due date (3) priority (4) manual (5) created")
+            .unwrap_or_default();
+        self.sort_order = match input.as_str() {
+            "1" => SortOrder::Id,
+            "2" => SortOrder::DueDate,
+            "3" => SortOrder::Priority,
+            "4" => SortOrder::Manual,
+            "5" => SortOrder::Created,
+            _ => return Err("Invalid option".to_string()),
+        };
+        self.view
+            .show_message(&format!("Sorting tasks by {}.", self.sort_order.as_str()));
+        Ok(())
+    }
+
+    pub fn set_auto_sort(&mut self) -> Result<(), String> {
+        let input = self
+            .view
+            .get_user_input("Always sort by (none/id/due/priority/created):")
+            .unwrap_or_default();
+        let order =
+            SortOrder::parse(&input).ok_or_else(|| format!("Unknown sort order '{}'.", input))?;
+        Config::store("auto_sort", order.key())
+            .map_err(|e| format!("Failed to save config: {}", e))?;
+        self.sort_order = order;
+        self.view
+            .show_message(&format!("Tasks will be sorted by {}.", order.as_str()));
+        Ok(())
+    }
+
+    pub fn focus(&mut self) -> Result<(), String> {
+        match self.lists.active().next_focus() {
+            Some(task) => {
+                self.view.show_message("Focus on:");
+                self.view.display_task(task, None);
+            }
+            None => self
+                .view
+                .show_message("Nothing to focus on — you're all caught up!"),
+        }
+        Ok(())
+    }
+
+    pub fn due_between(&mut self) -> Result<(), String> {
+        let Some(start) = self.prompt_date("Start date (YYYY-MM-DD):") else {
+            return Ok(());
+        };
+        let end = loop {
+            let Some(end) = self.prompt_date("End date (YYYY-MM-DD):") else {
+                return Ok(());
+            };
+            if date_utils::parse_date(&end) >= date_utils::parse_date(&start) {
+                break end;
+            }
+            self.view
+                .show_message(&format!("The end date must not be before {}.", start));
+        };
+        let tasks = self.lists.active().due_between(&start, &end);
+        if tasks.is_empty() {
+            self.view
+                .show_message(&format!("Nothing due from {} to {}.", start, end));
+        } else {
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    /// Reads a YYYY-MM-DD date, reprompting until valid. Blank cancels.
+    fn prompt_date(&self, prompt: &str) -> Option<String> {
+        loop {
+            let input = self.view.get_user_input(prompt).unwrap_or_default();
+            if input.is_empty() {
+                return None;
+            }
+            if date_utils::parse_date(&input).is_some() {
+                return Some(input);
+            }
+            self.view
+                .show_message(&format!("Invalid date '{}', expected YYYY-MM-DD.", input));
+        }
+    }
+
+    pub fn show_procrastinated(&mut self) -> Result<(), String> {
+        let tasks = self.lists.active().oldest_pending(PROCRASTINATED_LIMIT);
+        if tasks.is_empty() {
+            self.view.show_message("No pending tasks.");
+        } else {
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    pub fn show_today(&mut self) -> Result<(), String> {
+        let mut tasks = self.lists.active().due_today();
+        if self.view.confirm("Include overdue? (y/N)") {
+            // Tasks due earlier today are already listed above.
+            let overdue = self.lists.active().overdue();
+            let earlier: Vec<&Task> = overdue
+                .into_iter()
+                .filter(|task| !tasks.iter().any(|today| today.id == task.id))
+                .collect();
+            tasks.extend(earlier);
+        }
+        if tasks.is_empty() {
+            self.view.show_message("Nothing due today.");
+        } else {
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    pub fn done_since_last_run(&mut self) -> Result<(), String> {
+        let tasks = self.lists.active().completed_since_last_run();
+        if tasks.is_empty() {
+            self.view
+                .show_message("Nothing completed since the last run.");
+        } else {
+            self.view.show_message("Completed since the last run:");
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    pub fn filter_tasks(&mut self) -> Result<(), String> {
+        let input = self
+            .view
+            .get_user_input("Show: (1) pending (2) done (3) all")
+            .unwrap_or_default();
+        let tasks: Vec<&Task> = match input.as_str() {
+            "1" => self.lists.active().filter_by_done(false),
+            "2" => self.lists.active().filter_by_done(true),
+            "3" => self.lists.active().get_all().iter().collect(),
+            _ => return Err("Invalid option".to_string()),
+        };
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        Ok(())
+    }
+
+    pub fn search_tasks(&mut self) -> Result<(), String> {
+        let query = self
+            .view
+            .get_user_input("Enter search query:")
+            .unwrap_or_default();
+        let tasks = self.lists.active().search(&query);
+        if tasks.is_empty() {
+            self.view
+                .show_message(&format!("No tasks match '{}'.", query));
+        } else {
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    pub fn bulk_tag(&mut self) -> Result<(), String> {
+        let query = self
+            .view
+            .get_user_input("Search tasks to tag (blank for all):")
+            .unwrap_or_default();
+        let tasks = if query.trim().is_empty() {
+            self.lists.active().get_all().iter().collect()
+        } else {
+            self.lists.active().search(&query)
+        };
+        if tasks.is_empty() {
+            return Err(format!("No tasks match '{}'.", query));
+        }
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        let ids: Vec<u32> = tasks.iter().map(|task| task.id).collect();
+        let input = self
+            .view
+            .get_user_input("Tag all results with (prefix with - to remove, blank to skip):")
+            .unwrap_or_default();
+        let (tag, remove) = match input.strip_prefix('-') {
+            Some(tag) => (tag.trim(), true),
+            None => (input.trim(), false),
+        };
+        if tag.is_empty() {
+            return Ok(());
+        }
+        let affected = if remove {
+            self.lists.active_mut().remove_tag_from_many(&ids, tag)
+        } else {
+            self.lists.active_mut().add_tag_to_many(&ids, tag)
+        };
+        self.view.show_message(&format!(
+            "{} tag '{}' {} {} task(s).",
+            if remove { "Removed" } else { "Added" },
+            tag,
+            if remove { "from" } else { "to" },
+            affected
+        ));
+        Ok(())
+    }
+
+    pub fn filter_by_tag(&mut self) -> Result<(), String> {
+        let tag = self.view.get_user_input("Enter tag:").unwrap_or_default();
+        let tasks = self.lists.active().filter_by_tag(&tag);
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        Ok(())
+    }
+
+    pub fn filter_by_color(&mut self) -> Result<(), String> {
+        let label = self
+            .view
+            .get_user_input("Enter color label:")
+            .unwrap_or_default();
+        let tasks = self.lists.active().filter_by_color(&label);
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        Ok(())
+    }
+
+    pub fn export_csv(&mut self) -> Result<(), String> {
+        let path = self
+            .view
+            .get_user_input("Enter output filename:")
+            .unwrap_or_default();
+        if path.is_empty() {
+            return Ok(());
+        }
+        self.lists
+            .active()
+            .export_csv(&path)
+            .map_err(|e| format!("Failed to export tasks: {}", e))?;
+        self.view.show_message(&format!(
+            "Exported {} tasks to {}.",
+            self.lists.active().get_all().len(),
+            path
+        ));
+        Ok(())
+    }
+
+    pub fn export_markdown(&mut self) -> Result<(), String> {
+        let path = self
+            .view
+            .get_user_input("Enter Markdown output filename:")
+            .unwrap_or_default();
+        if path.is_empty() {
+            return Ok(());
+        }
+        self.lists
+            .active()
+            .export_markdown(&path)
+            .map_err(|e| format!("Failed to export tasks: {}", e))?;
+        self.view.show_message(&format!(
+            "Exported {} tasks to {}.",
+            self.lists.active().get_all().len(),
+            path
+        ));
+        Ok(())
+    }
+
+    pub fn export_json(&mut self) -> Result<(), String> {
+        let path = self
+            .view
+            .get_user_input("Enter JSON output filename:")
+            .unwrap_or_default();
+        if path.is_empty() {
+            return Ok(());
+        }
+        self.lists
+            .active()
+            .export_json(&path, true)
+            .map_err(|e| format!("Failed to export tasks: {}", e))?;
+        self.view.show_message(&format!(
+            "Exported {} tasks to {}.",
+            self.lists.active().get_all().len(),
+            path
+        ));
+        Ok(())
+    }
+
+    pub fn import_csv(&mut self) -> Result<(), String> {
+        let path = self
+            .view
+            .get_user_input("Enter CSV filename to import:")
+            .unwrap_or_default();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let count = self.lists.active_mut().import_csv(&path)?;
+        self.view
+            .show_message(&format!("Imported {} tasks from {}.", count, path));
+        Ok(())
+    }
+
+    pub fn import_text(&mut self) -> Result<(), String> {
+        let path = self
+            .view
+            .get_user_input("Enter text filename to import (one task per line):")
+            .unwrap_or_default();
+        if path.is_empty() {
+            return Ok(());
+        }
+        let count = self.lists.active_mut().import_lines(&path)?;
+        self.view
+            .show_message(&format!("Imported {} tasks from {}.", count, path));
+        Ok(())
+    }
+
+    pub fn quick_add(&mut self) -> Result<(), String> {
+        self.check_capacity()?;
+        let input = self
+            .view
+            .get_user_input("Quick add (e.g. Buy milk @home !high due:2024-06-12):")
+            .unwrap_or_default();
+        let parsed = parse_quick_add(&input);
+        if parsed.title.is_empty() {
+            return Err("Title cannot be empty; task not added.".to_string());
+        }
+        check_length("Title", &parsed.title, MAX_TITLE_LEN)?;
+        let task = parsed.into_task();
+        self.lists.active_mut().add_task(task.clone())?;
+        self.view.show_added(&task);
+        Ok(())
+    }
+
+    pub fn add_task(&mut self) -> Result<(), String> {
+        self.add_task_with(None)
+    }
+
+    pub fn repeat_last_add(&mut self) -> Result<(), String> {
+        let last = self
+            .last_added
+            .take()
+            .ok_or("No task has been added this session.")?;
+        self.view
+            .show_message("Press Enter to keep a value, or \"-\" to clear it.");
+        let result = self.add_task_with(Some(&last));
+        if self.last_added.is_none() {
+            self.last_added = Some(last);
+        }
+        result
+    }
+
+    /// Prompts for a new task, offering `last`'s fields as defaults.
+    fn add_task_with(&mut self, last: Option<&Task>) -> Result<(), String> {
+        self.check_capacity()?;
+        let title = self
+            .view
+            .get_input_or("Enter task title:", last.map(|task| task.title.as_str()));
+        if title.trim().is_empty() {
+            return Err("Title cannot be empty; task not added.".to_string());
+        }
+        check_length("Title", &title, MAX_TITLE_LEN)?;
+        // Repeating an add with the same title is deliberate, so don't ask about the duplicate.
+        let repeated = last.is_some_and(|last| last.title == title);
+        if let Some(existing) = self
+            .lists
+            .active()
+            .find_by_title(&title)
+            .filter(|_| !repeated)
+        {
+            let prompt = format!(
+                "A pending task titled '{}' already exists (id {}). Add anyway? (y/N)",
+                existing.title.trim(),
+                existing.id
+            );
+            if !self.view.confirm(&prompt) {
+                return Ok(());
+            }
+        }
+        let description = match last {
+            Some(last) if !last.description.is_empty() => {
+                let prompt = format!(
+                    "Enter task description [{}] (!edit for $EDITOR):",
+                    single_line(&last.description)
+                );
+                match self.view.get_description_input(&prompt).as_str() {
+                    "" => last.description.clone(),
+                    "-" => String::new(),
+                    input => self.edit_externally_if_asked(input, &last.description),
+                }
+            }
+            _ => {
+                let input = self
+                    .view
+                    .get_description_input("Enter task description (optional, !edit for $EDITOR):");
+                self.edit_externally_if_asked(&input, "")
+            }
+        };
+        check_length("Description", &description, MAX_DESCRIPTION_LEN)?;
+
+        let due_date = self.prompt_due_date(last.and_then(|task| task.due_date.as_deref()));
+        let priority = self.prompt_priority(last.map(|task| task.priority));
+        let recurrence = Recurrence::parse(
+            &self.view.get_input_or(
+                "Repeat (daily/weekly/monthly, blank for none):",
+                last.and_then(|task| task.recurrence)
+                    .map(|recurrence| recurrence.as_str()),
+            ),
+        );
+        let tags = parse_tags(&self.view.get_input_or(
+            "Enter tags (comma-separated, blank for none):",
+            last.map(|task| task.tags.join(", ")).as_deref(),
+        ));
+        let estimate_minutes = self.prompt_estimate(last.and_then(|task| task.estimate_minutes));
+        let color_label = self.view.get_input_or(
+            "Color label (e.g. red, blue, green; blank for none):",
+            last.and_then(|task| task.color_label.as_deref()),
+        );
+        let link = self.prompt_link(last.and_then(|task| task.link.as_deref()));
+
+        let date = date_utils::format_datetime(date_utils::now_secs());
+        let mut task = Task::new(title, description, date, false);
+        task.due_date = due_date;
+        task.priority = priority;
+        task.tags = tags;
+        task.recurrence = recurrence;
+        task.estimate_minutes = estimate_minutes;
+        task.color_label = (!color_label.is_empty()).then_some(color_label);
+        task.link = link;
+        self.last_added = Some(task.clone());
+        self.lists.active_mut().add_task(task.clone())?;
+        self.view.show_added(&task);
+        Ok(())
+    }
+
+    /// Swaps the "!edit" sentinel for text written in an external editor, falling
+    /// back to inline entry when no editor can be started.
+    fn edit_externally_if_asked(&self, input: &str, initial: &str) -> String {
+        if input.trim() != EDITOR_SENTINEL {
+            return input.to_string();
+        }
+        match self.view.edit_in_external_editor(initial) {
+            Ok(text) => text,
+            Err(e) => {
+                self.view
+                    .show_message(&format!("Could not open an editor: {}", e));
+                self.view.get_multiline_input("Enter task description:")
+            }
+        }
+    }
+
+    fn prompt_due_date(&self, default: Option<&str>) -> Option<String> {
+        loop {
+            let input = self.view.get_input_or(
+                "Enter due date (YYYY-MM-DD [HH:MM], blank for none):",
+                default,
+            );
+            if input.is_empty() {
+                return None;
+            }
+            if date_utils::parse_due(&input).is_some() {
+                return Some(input);
+            }
+            self.view.show_message(&format!(
+                "Invalid date '{}', expected YYYY-MM-DD [HH:MM].",
+                input
+            ));
+        }
+    }
+
+    fn prompt_link(&self, default: Option<&str>) -> Option<String> {
+        loop {
+            let input = self
+                .view
+                .get_input_or("Link (http:// or https://, blank for none):", default);
+            if input.is_empty() {
+                return None;
+            }
+            match check_link(&input) {
+                Ok(()) => return Some(input),
+                Err(e) => self.view.show_message(&e),
+            }
+        }
+    }
+
+    fn prompt_estimate(&self, default: Option<u32>) -> Option<u32> {
+        loop {
+            let input = self.view.get_input_or(
+                "Estimated effort in minutes (blank for none):",
+                default.map(|minutes| minutes.to_string()).as_deref(),
+            );
+            if input.is_empty() {
+                return None;
+            }
+            match input.parse::<u32>() {
+                Ok(minutes) => return Some(minutes),
+                Err(_) => self
+                    .view
+                    .show_message(&format!("Invalid number of minutes '{}'.", input)),
+            }
+        }
+    }
+
+    fn prompt_priority(&self, default: Option<Priority>) -> Priority {
+        let input = self.view.get_input_or(
+            "Enter priority (low/medium/high, blank for medium):",
+            default.map(|priority| priority.as_str()),
+        );
+        Priority::parse(&input).unwrap_or_default()
+    }
+
+    pub fn set_dependency(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter id of the blocked task:") else {
+            return Ok(());
+        };
+        let input = self
+            .view
+            .get_user_input("Enter id of the blocking task (blank to clear):")
+            .unwrap_or_default();
+        let blocker = if input.is_empty() {
+            None
+        } else {
+            Some(
+                input
+                    .parse::<u32>()
+                    .map_err(|_| format!("Invalid task id '{}'.", input))?,
+            )
+        };
+        self.lists.active_mut().set_dependency(id, blocker)?;
+        match blocker {
+            Some(blocker) => self
+                .view
+                .show_message(&format!("Task {} is now blocked by task {}.", id, blocker)),
+            None => self
+                .view
+                .show_message(&format!("Cleared dependency of task {}.", id)),
+        }
+        Ok(())
+    }
+
+    pub fn snooze_task(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to snooze:") else {
+            return Ok(());
+        };
+        let input = self
+            .view
+            .get_user_input("Snooze by (e.g. 1d, 3d, 1w):")
+            .unwrap_or_default();
+        let by = date_utils::parse_duration(&input)
+            .ok_or_else(|| format!("Invalid duration '{}', expected e.g. 1d or 1w.", input))?;
+        self.lists.active_mut().snooze(id, by)?;
+        let due = self
+            .lists
+            .active()
+            .get(id)
+            .and_then(|task| task.due_date.clone())
+            .unwrap_or_default();
+        self.view
+            .show_message(&format!("Task {} is now due {}.", id, due));
+        Ok(())
+    }
+
+    pub fn toggle_pin(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to pin or unpin:") else {
+            return Ok(());
+        };
+        let pinned = self.lists.active_mut().toggle_pin(id)?;
+        let state = if pinned { "Pinned" } else { "Unpinned" };
+        self.view.show_message(&format!("{} task {}.", state, id));
+        Ok(())
+    }
+
+    pub fn open_link(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to open the link of:") else {
+            return Ok(());
+        };
+        let link = self
+            .lists
+            .active()
+            .get(id)
+            .and_then(|task| task.link.clone())
+            .ok_or_else(|| format!("Task {} has no link.", id))?;
+        self.view
+            .show_message(&Glyphs::label(self.view.glyphs.link, &link));
+        let opener = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        let opened = std::process::Command::new(opener)
+            .arg(&link)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if opened {
+            self.view.show_message("Opened in your browser.");
+        }
+        Ok(())
+    }
+
+    pub fn add_note(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to add a note to:") else {
+            return Ok(());
+        };
+        let note = self.view.get_user_input("Enter note:").unwrap_or_default();
+        if note.is_empty() {
+            return Err("Note cannot be empty.".to_string());
+        }
+        self.lists.active_mut().add_note(id, note)?;
+        self.view
+            .show_message(&format!("Added note to task {}.", id));
+        Ok(())
+    }
+
+    pub fn add_subtask(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to add a subtask to:") else {
+            return Ok(());
+        };
+        let title = self
+            .view
+            .get_user_input("Enter subtask:")
+            .unwrap_or_default();
+        if title.is_empty() {
+            return Err("Subtask cannot be empty.".to_string());
+        }
+        check_length("Subtask", &title, MAX_TITLE_LEN)?;
+        let number = self.lists.active_mut().add_subtask(id, title)?;
+        self.view
+            .show_message(&format!("Added subtask #{} to task {}.", number, id));
+        Ok(())
+    }
+
+    pub fn toggle_subtask(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id:") else {
+            return Ok(());
+        };
+        let input = self
+            .view
+            .get_user_input("Enter subtask number to toggle:")
+            .unwrap_or_default();
+        let number = input
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid subtask number '{}'.", input))?;
+        let all_done = self.lists.active_mut().toggle_subtask(id, number)?;
+        let parent_open = self.lists.active().get(id).is_some_and(|task| !task.done);
+        if all_done
+            && parent_open
+            && self
+                .view
+                .confirm(&format!("All subtasks done. Mark task {} done? (y/N)", id))
+        {
+            self.lists.active_mut().mark_done(id)?;
+            self.last_completed = Some(id);
+            self.view.show_message(&format!("Marked task {} done.", id));
+        }
+        Ok(())
+    }
+
+    pub fn set_priority(&mut self) -> Result<(), String> {
+        let Some(id) = self.view.get_id_input("Enter task id to re-prioritize:") else {
+            return Ok(());
+        };
+        let priority = self.prompt_priority(None);
+        self.lists.active_mut().set_priority(id, priority)?;
+        Ok(())
+    }
+
+    pub fn delete_task(&mut self) -> Result<(), String> {
+        let Some(ids) = self.prompt_id_list("Enter task id(s) to delete (e.g. 1-3,5):") else {
+            return Ok(());
+        };
+        let mut removed = Vec::new();
+        let mut missing = Vec::new();
+        for id in ids {
+            match self.lists.active_mut().archive(id) {
+                Ok(index) => removed.push((index, id)),
+                Err(_) => missing.push(id),
+            }
+        }
+        if !removed.is_empty() {
+            self.view
+                .show_message(&format!("Archived {} task(s).", removed.len()));
+            self.record(UndoAction::Archive(removed));
+        }
+        self.lists.active().missing_ids_result(&missing)
+    }
+
+    /// Reads ids, or lets the user pick one by position after entering "p".
+    fn prompt_id_list(&self, prompt: &str) -> Option<Vec<u32>> {
+        let prompt = &format!("{} [p to pick from a list]", prompt);
+        let input = self.view.get_user_input(prompt).unwrap_or_default();
+        if input.is_empty() {
+            return None;
+        }
+        if input.eq_ignore_ascii_case("p") {
+            return self
+                .view
+                .pick_task(self.lists.active().get_all())
+                .map(|id| vec![id]);
+        }
+        match parse_id_list(&input) {
+            Ok(ids) => return Some(ids),
+            Err(e) => self.view.show_message(&e),
+        }
+
+        let tasks: Vec<&Task> = self.lists.active().get_all().iter().collect();
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        let input = self.view.get_user_input(prompt).unwrap_or_default();
+        parse_id_list(&input).ok()
+    }
+
+    fn apply_to_ids(
+        &mut self,
+        ids: &[u32],
+        action: fn(&mut TasksModel, u32) -> Result<(), String>,
+    ) -> Vec<u32> {
+        let mut missing = Vec::new();
+        for &id in ids {
+            match action(self.lists.active_mut(), id) {
+                Ok(()) => {}
+                Err(_) if self.lists.active().get(id).is_none() => missing.push(id),
+                Err(e) => self.view.show_message(&e),
+            }
+        }
+        missing
+    }
+
+    fn prompt_existing_id(&self, prompt: &str) -> Option<u32> {
+        let id = self.view.get_id_input(prompt)?;
+        if self.lists.active().get(id).is_some() {
+            return Some(id);
+        }
+        self.view.show_message(&self.lists.active().not_found(id));
+
+        let tasks: Vec<&Task> = self.lists.active().get_all().iter().collect();
+        self.view
+            .display_tasks(&tasks, self.lists.active().get_all());
+        self.view.get_id_input(prompt)
+    }
+
+    pub fn toggle_status(&mut self) -> Result<(), String> {
+        let Some(ids) = self.prompt_id_list("Enter task id(s) to toggle (e.g. 3 5 8 or 1-3):")
+        else {
+            return Ok(());
+        };
+        let missing = self.apply_to_ids(&ids, TasksModel::toggle);
+        self.remember_completed(&ids);
+        self.lists.active().missing_ids_result(&missing)
+    }
+
+    pub fn mark_done(&mut self) -> Result<(), String> {
+        let Some(ids) = self.prompt_id_list("Enter task id(s) to mark done (e.g. 1-3,5):") else {
+            return Ok(());
+        };
+        let missing = self.apply_to_ids(&ids, TasksModel::mark_done);
+        self.remember_completed(&ids);
+        self.lists.active().missing_ids_result(&missing)
+    }
+
+    /// Records the last of `ids` that actually ended up done, for "reopen last completed".
+    fn remember_completed(&mut self, ids: &[u32]) {
+        if let Some(&id) = ids
+            .iter()
+            .rev()
+            .find(|&&id| self.lists.active().get(id).is_some_and(|task| task.done))
+        {
+            self.last_completed = Some(id);
+        }
+    }
+
+    pub fn reopen_last_completed(&mut self) -> Result<(), String> {
+        let id = self
+            .last_completed
+            .ok_or("No task has been completed this session.")?;
+        match self.lists.active().get(id) {
+            None => return Err(format!("Task {} was deleted after being completed.", id)),
+            Some(task) if !task.done => return Err(format!("Task {} is already open.", id)),
+            Some(_) => {}
+        }
+        self.lists.active_mut().mark_not_done(id)?;
+        self.last_completed = None;
+        self.view.show_message(&format!("Reopened task {}.", id));
+        Ok(())
+    }
+
+    pub fn mark_not_done(&mut self) -> Result<(), String> {
+        let Some(ids) = self.prompt_id_list("Enter task id(s) to mark not done (e.g. 1-3,5):")
+        else {
+            return Ok(());
+        };
+        let missing = self.apply_to_ids(&ids, TasksModel::mark_not_done);
+        self.lists.active().missing_ids_result(&missing)
+    }
+
+    pub fn edit_task(&mut self) -> Result<(), String> {
+        let Some(id) = self.view.get_id_input("Enter task id to edit:") else {
+            return Ok(());
+        };
+        let task = self
+            .lists
+            .active()
+            .get(id)
+            .ok_or_else(|| self.lists.active().not_found(id))?;
+        self.view
+            .display_tasks(&[task], self.lists.active().get_all());
+
+        let title = self
+            .view
+            .get_user_input("New title (blank to keep):")
+            .unwrap_or_default();
+        let current = task.description.clone();
+        let description = self
+            .view
+            .get_user_input("New description (blank to keep, !edit for $EDITOR):")
+            .unwrap_or_default();
+        let description = self.edit_externally_if_asked(&description, &current);
+        check_length("Title", &title, MAX_TITLE_LEN)?;
+        check_length("Description", &description, MAX_DESCRIPTION_LEN)?;
+        let title = (!title.is_empty()).then_some(title);
+        let description = (!description.is_empty()).then_some(description);
+        let due_date = self.prompt_new_due_date();
+        let link = self.prompt_new_link();
+
+        self.lists.active_mut().edit(id, title, description)?;
+        if let Some(due_date) = due_date {
+            self.lists.active_mut().set_due_date(id, due_date)?;
+        }
+        if let Some(link) = link {
+            self.lists.active_mut().set_link(id, link)?;
+        }
+        Ok(())
+    }
+
+    pub fn choose_task(&mut self) -> Result<(), String> {
+        match self.prompt_existing_id("Enter task id to work on:") {
+            Some(id) => self.task_menu(id),
+            None => Ok(()),
+        }
+    }
+
+    pub fn task_menu(&mut self, id: u32) -> Result<(), String> {
+        loop {
+            let Some(task) = self.lists.active().get(id) else {
+                self.view
+                    .show_message(&format!("Task {} no longer exists.", id));
+                return Ok(());
+            };
+            self.view
+                .display_tasks(&[task], self.lists.active().get_all());
+            self.view.show_task_menu(id);
+            let input = self
+                .view
+                .get_user_input("Select an option:")
+                .unwrap_or_default();
+            let result = match input.as_str() {
+                "" | "0" | "b" | "B" => return Ok(()),
+                "1" => {
+                    let title = self.view.get_user_input("New title:").unwrap_or_default();
+                    if title.is_empty() {
+                        Err("Title cannot be empty.".to_string())
+                    } else {
+                        check_length("Title", &title, MAX_TITLE_LEN)
+                            .and_then(|()| self.lists.active_mut().edit(id, Some(title), None))
+                    }
+                }
+                "2" => self.lists.active_mut().toggle(id).map(|()| {
+                    self.remember_completed(&[id]);
+                }),
+                "3" => {
+                    let note = self.view.get_user_input("Enter note:").unwrap_or_default();
+                    if note.is_empty() {
+                        Err("Note cannot be empty.".to_string())
+                    } else {
+                        self.lists.active_mut().add_note(id, note)
+                    }
+                }
+                "4" => match self.prompt_new_due_date() {
+                    Some(due_date) => self.lists.active_mut().set_due_date(id, due_date),
+                    None => Ok(()),
+                },
+                "5" => self.lists.active_mut().archive(id).map(|index| {
+                    self.record(UndoAction::Archive(vec![(index, id)]));
+                }),
+                _ => Err("Invalid option, try again.".to_string()),
+            };
+            let result = if self.autosave {
+                result.and_then(|()| self.flush())
+            } else {
+                result
+            };
+            if let Err(e) = result {
+                self.view.show_message(&e);
+            }
+        }
+    }
+
+    fn prompt_new_due_date(&self) -> Option<Option<String>> {
+        loop {
+            let input = self
+                .view
+                .get_user_input(
+                    "New due date (YYYY-MM-DD [HH:MM], blank to keep, 'none' to clear):",
+                )
+                .unwrap_or_default();
+            if input.is_empty() {
+                return None;
+            }
+            if input.eq_ignore_ascii_case("none") {
+                return Some(None);
+            }
+            if date_utils::parse_due(&input).is_some() {
+                return Some(Some(input));
+            }
+            self.view.show_message(&format!(
+                "Invalid date '{}', expected YYYY-MM-DD [HH:MM].",
+                input
+            ));
+        }
+    }
+
+    fn prompt_new_link(&self) -> Option<Option<String>> {
+        loop {
+            let input = self
+                .view
+                .get_user_input("New link (blank to keep, 'none' to clear):")
+                .unwrap_or_default();
+            if input.is_empty() {
+                return None;
+            }
+            if input.eq_ignore_ascii_case("none") {
+                return Some(None);
+            }
+            match check_link(&input) {
+                Ok(()) => return Some(Some(input)),
+                Err(e) => self.view.show_message(&e),
+            }
+        }
+    }
+
+    fn move_task(
+        &mut self,
+        action: fn(&mut TasksModel, u32) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to move:") else {
+            return Ok(());
+        };
+        action(self.lists.active_mut(), id)?;
+        self.sort_order = SortOrder::Manual;
+        self.show_tasks()
+    }
+
+    pub fn duplicate_task(&mut self) -> Result<(), String> {
+        self.check_capacity()?;
+        let Some(id) = self.prompt_existing_id("Enter task id to duplicate:") else {
+            return Ok(());
+        };
+        let new_id = self.lists.active_mut().duplicate(id)?;
+        self.view
+            .show_message(&format!("Duplicated task {} as task {}.", id, new_id));
+        Ok(())
+    }
+
+    pub fn delete_tasks(&mut self) -> Result<(), String> {
+        let count = self.lists.active().get_all().len();
+        if count > self.confirm_clear_threshold && !self.view.confirm("Are you sure? (y/N):") {
+            self.view.show_message("Cancelled.");
+            return Ok(());
+        }
+        let ids: Vec<u32> = self
+            .lists
+            .active()
+            .get_all()
+            .iter()
+            .map(|task| task.id)
+            .collect();
+        let (archived, _) = self.lists.active_mut().archive_many(&ids);
+        self.view
+            .show_message(&format!("Cleared {} tasks.", archived));
+        // Archiving front to back takes every task from index 0, and undo restores in reverse.
+        self.record(UndoAction::Archive(
+            ids.into_iter().map(|id| (0, id)).collect(),
+        ));
+        Ok(())
+    }
+
+    pub fn delete_by_tag(&mut self) -> Result<(), String> {
+        let tag = self
+            .view
+            .get_user_input("Delete tasks tagged:")
+            .unwrap_or_default();
+        if tag.trim().is_empty() {
+            return Ok(());
+        }
+        let count = self.lists.active().filter_by_tag(&tag).len();
+        if count == 0 {
+            self.view
+                .show_message(&format!("No tasks are tagged '{}'.", tag));
+            return Ok(());
+        }
+        let prompt = format!("Delete {} task(s) tagged '{}'? (y/N):", count, tag);
+        if !self.view.confirm(&prompt) {
+            self.view.show_message("Cancelled.");
+            return Ok(());
+        }
+        let removed = self.lists.active_mut().delete_by_tag(&tag);
+        self.view
+            .show_message(&format!("Archived {} task(s) tagged '{}'.", removed, tag));
+        Ok(())
+    }
+
+    pub fn delete_completed(&mut self) -> Result<(), String> {
+        let removed = self.lists.active_mut().delete_completed();
+        self.view
+            .show_message(&format!("Archived {} completed tasks.", removed));
+        Ok(())
+    }
+
+    fn prompt_list_name(&self, prompt: &str) -> String {
+        self.view.show_message(&format!(
+            "Lists: {} (active: {})",
+            self.lists.names().join(", "),
+            self.lists.active_name()
+        ));
+        self.view.get_user_input(prompt).unwrap_or_default()
+    }
+
+    pub fn compact_ids(&mut self) -> Result<(), String> {
+        let prompt = "This renumbers every task from 1 and breaks any ids you noted elsewhere. Continue? (y/N):";
+        if !self.view.confirm(prompt) {
+            self.view.show_message("Cancelled.");
+            return Ok(());
+        }
+        self.lists.active_mut().compact_ids(self.sort_order);
+        self.lists.sync_id_counter();
+        // Undo entries and the last completion refer to the old ids.
+        self.history.clear();
+        self.last_completed = None;
+        let count = self.lists.active().get_all().len();
+        self.view
+            .show_message(&format!("Renumbered {} task(s) from 1.", count));
+        Ok(())
+    }
+
+    pub fn switch_list(&mut self) -> Result<(), String> {
+        let name = self.prompt_list_name("Enter list to switch to:");
+        if name.is_empty() {
+            return Ok(());
+        }
+        self.lists.switch(&name)?;
+        // Undo entries refer to positions in the previous list.
+        self.history.clear();
+        self.last_completed = None;
+        self.view
+            .show_message(&format!("Switched to list '{}'.", name));
+        Ok(())
+    }
+
+    pub fn manage_lists(&mut self) -> Result<(), String> {
+        let choice = self
+            .view
+            .get_user_input("(c)reate, (d)elete or (r)ename a list:")
+            .unwrap_or_default();
+        match choice.to_lowercase().as_str() {
+            "c" | "create" => {
+                let name = self.prompt_list_name("Name of the new list:");
+                self.lists.create(&name)?;
+                self.view.show_message(&format!("Created list '{}'.", name));
+            }
+            "d" | "delete" => {
+                let name = self.prompt_list_name("List to delete:");
+                let prompt = format!("Delete list '{}' and all its tasks? (y/N):", name);
+                if !self.view.confirm(&prompt) {
+                    self.view.show_message("Cancelled.");
+                    return Ok(());
+                }
+                self.lists.delete(&name)?;
+                self.view.show_message(&format!("Deleted list '{}'.", name));
+            }
+            "r" | "rename" => {
+                let old = self.prompt_list_name("List to rename:");
+                let new = self.view.get_user_input("New name:").unwrap_or_default();
+                self.lists.rename(&old, &new)?;
+                self.view
+                    .show_message(&format!("Renamed list '{}' to '{}'.", old, new));
+            }
+            "" => {}
+            _ => return Err("Invalid option".to_string()),
+        }
+        Ok(())
+    }
+
+    pub fn weekly_report(&mut self) -> Result<(), String> {
+        let report = self.lists.active().report_last_days(7);
+        self.view.show_message("Completed in the last 7 days:");
+        for (day, count) in &report {
+            self.view.show_message(&format!("  {}: {}", day, count));
+        }
+        let total: usize = report.iter().map(|(_, count)| count).sum();
+        let (_, _, pending) = self.lists.active().stats();
+        self.view.show_message(&format!(
+            "Total completed: {} | Still pending: {}",
+            total, pending
+        ));
+        Ok(())
+    }
+
+    pub fn save_template(&mut self) -> Result<(), String> {
+        let Some(id) = self.prompt_existing_id("Enter task id to save as template:") else {
+            return Ok(());
+        };
+        let name = self
+            .view
+            .get_user_input("Template name:")
+            .unwrap_or_default();
+        if name.is_empty() {
+            return Err("Template name cannot be empty.".to_string());
+        }
+        self.lists.active_mut().save_template(&name, id)?;
+        self.view
+            .show_message(&format!("Saved task {} as template '{}'.", id, name));
+        Ok(())
+    }
+
+    pub fn new_from_template(&mut self) -> Result<(), String> {
+        self.check_capacity()?;
+        let names = self.lists.active().template_names();
+        if names.is_empty() {
+            self.view.show_message("No templates saved yet.");
+            return Ok(());
+        }
+        self.view
+            .show_message(&format!("Templates: {}", names.join(", ")));
+        let name = self
+            .view
+            .get_user_input("Template name:")
+            .unwrap_or_default();
+        if name.is_empty() {
+            return Ok(());
+        }
+        let id = self.lists.active_mut().add_from_template(&name)?;
+        self.view
+            .show_message(&format!("Added task {} from template '{}'.", id, name));
+        Ok(())
+    }
+
+    pub fn show_archive(&mut self) -> Result<(), String> {
+        let tasks: Vec<&Task> = self.lists.active().get_archived().iter().collect();
+        if tasks.is_empty() {
+            self.view.show_message("The archive is empty.");
+        } else {
+            self.view
+                .display_tasks(&tasks, self.lists.active().get_all());
+        }
+        Ok(())
+    }
+
+    pub fn restore_archived(&mut self) -> Result<(), String> {
+        let Some(id) = self.view.get_id_input("Enter archived task id to restore:") else {
+            return Ok(());
+        };
+        self.lists.active_mut().restore(id)?;
+        self.view.show_message(&format!("Restored task {}.", id));
+        Ok(())
+    }
+
+    pub fn purge_archive(&mut self) -> Result<(), String> {
+        let count = self.lists.active().get_archived().len();
+        if count == 0 {
+            self.view.show_message("The archive is empty.");
+            return Ok(());
+        }
+        let prompt = format!("Permanently delete {} archived task(s)? (y/N):", count);
+        if !self.view.confirm(&prompt) {
+            self.view.show_message("Cancelled.");
+            return Ok(());
+        }
+        self.lists.active_mut().purge_archive();
+        self.view
+            .show_message(&format!("Permanently deleted {} task(s).", count));
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> Result<(), String> {
+        let restored = match self.history.pop() {
+            Some(UndoAction::Archive(removed)) => {
+                let count = removed.len();
+                for (index, id) in removed.into_iter().rev() {
+                    self.lists.active_mut().restore_at(index, id)?;
+                }
+                count
+            }
+            None => return Err("Nothing to undo.".to_string()),
+        };
+        self.view
+            .show_message(&format!("Restored {} task(s).", restored));
+        Ok(())
+    }
+}
+
+const USAGE: &str = "\
+Usage: rust-todo [--list <name>] [COMMAND]
+
+Without a command, starts the interactive menu. --list selects (or creates)
+a named task list; the default list is used otherwise.
+
+Commands:
+  add <title> [description]          Add a task
+  quick <text>                       Add a task with @tag, !priority and due:DATE tokens
+  list [--plain | --json]            List all tasks (--plain: tab-separated, --json: JSON array)
+  done <ids>                         Mark tasks done (ids like 1-3,5)
+  rm <ids>                           Move tasks to the archive
+  count [--all | --done]             Print the number of pending (or all / done) tasks
+  clear [--completed] [--yes] [--dry-run]
+                                     Archive all (or only completed) tasks
+  --script <path> [--strict]         Run commands from a file, one per line
+  -h, --help                         Show this help
+";
+
+const COMMANDS: &[&str] = &[
+    "add", "quick", "list", "count", "done", "rm", "clear", "--script", "--help", "-h",
+];
+
+/// Splits a script line into arguments, honouring single and double quotes.
+fn split_command_line(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("Unterminated quote.".to_string());
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+fn run_script(model: &mut TasksModel, path: &str, strict: bool) -> Result<(), String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let (mut succeeded, mut failed) = (0, 0);
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = split_command_line(line).and_then(|args| {
+            // A script that runs itself would recurse until the stack overflows.
+            if args.first().is_some_and(|arg| arg == "--script") {
+                return Err("Scripts cannot run other scripts.".to_string());
+            }
+            run_command(model, &args)
+        });
+        match result {
+            Ok(()) => succeeded += 1,
+            Err(e) if strict => return Err(format!("Line {}: {}", number + 1, e)),
+            Err(e) => {
+                eprintln!("Line {}: {}", number + 1, e);
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "Script finished: {} command(s) succeeded, {} failed.",
+        succeeded, failed
+    );
+    Ok(())
+}
+
+fn run_command(model: &mut TasksModel, args: &[String]) -> Result<(), String> {
+    match args[0].as_str() {
+        "--help" | "-h" => print!("{}", USAGE),
+        "--script" => {
+            let path = args.get(1).ok_or("Missing script path.")?;
+            let strict = args[2..].iter().any(|arg| arg == "--strict");
+            run_script(model, path, strict)?;
+        }
+        "add" => {
+            let title = args.get(1).map(|title| title.trim()).unwrap_or_default();
+            if title.is_empty() {
+                return Err("Missing task title.".to_string());
+            }
+            let description = args.get(2).cloned().unwrap_or_default();
+            check_length("Title", title, MAX_TITLE_LEN)?;
+            check_length("Description", &description, MAX_DESCRIPTION_LEN)?;
+            let date = date_utils::format_datetime(date_utils::now_secs());
+            let task = Task::new(title.to_string(), description, date, false);
+            let id = task.id;
+            model.add_task(task)?;
+            model
+                .save()
+                .map_err(|e| format!("Failed to save tasks: {}", e))?;
+            println!("Added task {}.", id);
+        }
+        "quick" => {
+            let parsed = parse_quick_add(&args[1..].join(" "));
+            if parsed.title.is_empty() {
+                return Err("Missing task title.".to_string());
+            }
+            check_length("Title", &parsed.title, MAX_TITLE_LEN)?;
+            let task = parsed.into_task();
+            let id = task.id;
+            model.add_task(task)?;
+            model
+                .save()
+                .map_err(|e| format!("Failed to save tasks: {}", e))?;
+            println!("Added task {}.", id);
+        }
+        "list" if args[1..].iter().any(|arg| arg == "--json") => {
+            // A closed pipe isn't an error worth reporting here either.
+            let tasks = model.display_order(Config::load().auto_sort);
+            let value = json::Value::Array(tasks.into_iter().map(Task::to_json).collect());
+            let _ = writeln!(io::stdout().lock(), "{}", json::stringify(&value));
+        }
+        "list" if args[1..].iter().any(|arg| arg == "--plain") => {
+            let mut out = io::stdout().lock();
+            for task in model.display_order(Config::load().auto_sort) {
+                let status = if task.done { "done" } else { "pending" };
+                // Stop quietly when the reader (e.g. `head`) closes the pipe.
+                let written = writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    task.id,
+                    status,
+                    single_line(&task.title),
+                    task.due_date.as_deref().unwrap_or("")
+                );
+                if written.is_err() {
+                    break;
+                }
+            }
+        }
+        "list" => {
+            let config = Config::load();
+            let tasks = model.sorted(config.auto_sort);
+            let mut view = CliView::new();
+            view.page_size = 0;
+            view.apply_config(&config);
+            view.display_tasks(&tasks, model.get_all());
+            view.display_stats(model.stats(), model.overdue_count(), model.total_estimate());
+        }
+        "count" => {
+            let (total, done, pending) = model.stats();
+            let count = match args.get(1).map(String::as_str) {
+                None => pending,
+                Some("--all") => total,
+                Some("--done") => done,
+                Some(other) => return Err(format!("Unknown option '{}'.", other)),
+            };
+            println!("{}", count);
+        }
+        "done" => {
+            let ids = parse_id_list(args.get(1).ok_or("Missing task id.")?)?;
+            let mut missing = Vec::new();
+            for id in ids {
+                match model.mark_done(id) {
+                    Ok(()) => println!("Task {} marked done.", id),
+                    Err(_) if model.get(id).is_none() => missing.push(id),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+            model
+                .save()
+                .map_err(|e| format!("Failed to save tasks: {}", e))?;
+            model.missing_ids_result(&missing)?;
+        }
+        "rm" => {
+            let ids = parse_id_list(args.get(1).ok_or("Missing task id.")?)?;
+            let (archived, missing) = model.archive_many(&ids);
+            model
+                .save()
+                .map_err(|e| format!("Failed to save tasks: {}", e))?;
+            println!("Archived {} task(s).", archived);
+            model.missing_ids_result(&missing)?;
+        }
+        "clear" => {
+            let has_flag = |flag: &str| args[1..].iter().any(|arg| arg == flag);
+            let completed_only = has_flag("--completed");
+            let targets: Vec<&Task> = model
+                .get_all()
+                .iter()
+                .filter(|task| !completed_only || task.done)
+                .collect();
+            if has_flag("--dry-run") {
+                for task in &targets {
+                    println!(
+                        "Would delete task {}: {}",
+                        task.id,
+                        single_line(&task.title)
+                    );
+                }
+                println!("{} task(s) would be deleted.", targets.len());
+                return Ok(());
+            }
+            if !has_flag("--yes") {
+                if !io::stdin().is_terminal() {
+                    return Err("Refusing to clear without --yes.".to_string());
+                }
+                let prompt = format!("Delete {} task(s)? (y/N):", targets.len());
+                if !CliView::new().confirm(&prompt) {
+                    println!("Cancelled.");
+                    return Ok(());
+                }
+            }
+            let removed = if completed_only {
+                model.delete_completed()
+            } else {
+                let ids: Vec<u32> = model.get_all().iter().map(|task| task.id).collect();
+                model.archive_many(&ids).0
+            };
+            model
+                .save()
+                .map_err(|e| format!("Failed to save tasks: {}", e))?;
+            println!("Archived {} task(s).", removed);
+        }
+        other => return Err(format!("Unknown command '{}'.", other)),
+    }
+    Ok(())
+}
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut list_name = DEFAULT_LIST.to_string();
+    if args.first().is_some_and(|arg| arg == "--list") {
+        let Some(name) = args.get(1).cloned() else {
+            eprintln!("Missing list name.");
+            std::process::exit(1);
+        };
+        list_name = name;
+        args.drain(..2);
+    }
+    // Answer these before loading anything, so they never touch the data files.
+    if args
+        .first()
+        .is_some_and(|arg| arg == "--help" || arg == "-h")
+    {
+        print!("{}", USAGE);
+        return;
+    }
+    if args
+        .first()
+        .is_some_and(|arg| !COMMANDS.contains(&arg.as_str()))
+    {
+        eprintln!("Unknown command '{}'.\n\n{}", args[0], USAGE);
+        std::process::exit(1);
+    }
+    let mut lists = match ListManager::load(&list_name) {
+        Ok(lists) => lists,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let config = Config::load();
+    lists.set_max_tasks(config.max_tasks);
+    let archived = lists.auto_archive(config.auto_archive_done_after_days, date_utils::now_secs());
+    let archive_note = (archived > 0).then(|| {
+        format!(
+            "Archived {} task(s) completed more than {} day(s) ago.",
+            archived, config.auto_archive_done_after_days
+        )
+    });
+
+    if !args.is_empty() {
+        // Keep stdout clean for `list --json` and friends.
+        if let Some(note) = &archive_note {
+            eprintln!("{}", note);
+            if let Err(e) = lists.flush() {
+                eprintln!("Failed to save tasks: {}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Err(e) = run_command(lists.active_mut(), &args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    // Piped menu input is fine; only bail out when there is nothing to read at all.
+    // `fill_buf` peeks without consuming, so the menu still sees the first line.
+    let stdin_empty = !io::stdin().is_terminal()
+        && io::stdin()
+            .lock()
+            .fill_buf()
+            .map_or(true, |buffer| buffer.is_empty());
+    if stdin_empty {
+        println!(
+            "No input provided and not running interactively; use arguments or pipe commands."
+        );
+        return;
+    }
+
+    let mut view = CliView::new();
+    view.apply_config(&config);
+    if let Some(note) = &archive_note {
+        view.show_message(note);
+    }
+    view.display_overdue_banner(&lists.active().overdue());
+    let recent = lists.active().completed_since_last_run();
+    if !recent.is_empty() {
+        view.show_message("Completed since the last run:");
+        view.display_tasks(&recent, lists.active().get_all());
+    }
+    let mut presenter = Presenter::new(lists, view, &config);
 
     presenter.interaction_loop();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    struct ScriptedIo {
+        input: VecDeque<String>,
+        output: Rc<RefCell<Vec<String>>>,
+        _cleanup: Option<TempFile>,
+    }
+    impl InputOutput for ScriptedIo {
+        fn read_line(&mut self) -> Option<String> {
+            self.input.pop_front()
+        }
+
+        fn write_line(&mut self, line: &str) {
+            self.output.borrow_mut().push(line.to_string());
+        }
+    }
+
+    /// Removes a test's data file or directory (and any leftover save temp file)
+    /// when dropped, even if an assertion failed first.
+    struct TempFile(PathBuf);
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let mut temp = self.0.clone().into_os_string();
+            temp.push(".tmp");
+            for path in [self.0.clone(), PathBuf::from(temp)] {
+                let _ = if path.is_dir() {
+                    fs::remove_dir_all(&path)
+                } else {
+                    fs::remove_file(&path)
+                };
+            }
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("rust_todo_{}_{}.json", name, std::process::id()))
+    }
+
+    fn empty_model(name: &str) -> TasksModel {
+        let path = temp_path(name);
+        let _ = fs::remove_file(&path);
+        TasksModel::load_from(path)
+    }
+
+    fn scripted_presenter(name: &str, input: &[&str]) -> (Presenter, Rc<RefCell<Vec<String>>>) {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let io = ScriptedIo {
+            input: input.iter().map(|line| format!("{}\n", line)).collect(),
+            output: Rc::clone(&output),
+            _cleanup: Some(TempFile(temp_path(name))),
+        };
+        let view = CliView::with_io(Box::new(io));
+        let lists = ListManager::with_model(DEFAULT_LIST, empty_model(name));
+        let presenter = Presenter::new(lists, view, &Config::default());
+        (presenter, output)
+    }
+
+    fn task(title: &str) -> Task {
+        Task::new(title.to_string(), String::new(), String::new(), false)
+    }
+
+    #[test]
+    fn add_appends_task() {
+        let mut model = empty_model("add");
+        model.add(task("Buy milk"));
+        assert_eq!(model.get_all().len(), 1);
+        assert_eq!(model.get_all()[0].title, "Buy milk");
+    }
+
+    #[test]
+    fn delete_removes_only_matching_task() {
+        let mut model = empty_model("delete");
+        let keep = task("Keep");
+        let remove = task("Remove");
+        let (keep_id, remove_id) = (keep.id, remove.id);
+        model.add(keep);
+        model.add(remove);
+
+        let (_, removed) = model.delete(remove_id).unwrap();
+        assert_eq!(removed.title, "Remove");
+        assert_eq!(model.get_all().len(), 1);
+        assert_eq!(model.get_all()[0].id, keep_id);
+    }
+
+    #[test]
+    fn delete_unknown_id_is_an_error() {
+        let mut model = empty_model("delete_unknown");
+        assert_eq!(
+            model.delete(u32::MAX).err(),
+            Some(format!(
+                "Task with id {} not found. No tasks exist.",
+                u32::MAX
+            ))
+        );
+    }
+
+    #[test]
+    fn not_found_suggests_the_nearest_id() {
+        let mut model = empty_model("not_found");
+        let item = task("Only");
+        let id = item.id;
+        model.add(item);
+        assert_eq!(
+            model.toggle(id + 3).err(),
+            Some(format!(
+                "Task with id {} not found. Did you mean id {}?",
+                id + 3,
+                id
+            ))
+        );
+    }
+
+    #[test]
+    fn toggle_flips_done() {
+        let mut model = empty_model("toggle");
+        let item = task("Toggle me");
+        let id = item.id;
+        model.add(item);
+
+        model.toggle(id).unwrap();
+        assert!(model.get(id).unwrap().done);
+        model.toggle(id).unwrap();
+        assert!(!model.get(id).unwrap().done);
+    }
+
+    #[test]
+    fn toggle_unknown_id_is_an_error() {
+        let mut model = empty_model("toggle_unknown");
+        assert!(model.toggle(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn move_up_and_down_swap_neighbours() {
+        let mut model = empty_model("move");
+        let (first, second) = (task("first"), task("second"));
+        let (first_id, second_id) = (first.id, second.id);
+        model.add(first);
+        model.add(second);
+
+        model.move_up(second_id).unwrap();
+        let order: Vec<u32> = model.get_all().iter().map(|item| item.id).collect();
+        assert_eq!(order, vec![second_id, first_id]);
+        assert!(model.move_up(second_id).is_err());
+        assert!(model.move_down(first_id).is_err());
+    }
+
+    #[test]
+    fn completion_percent_rounds_to_nearest() {
+        let mut model = empty_model("completion");
+        assert_eq!(model.completion_percent(), 0);
+        let done = task("Done");
+        let done_id = done.id;
+        model.add(done);
+        model.add(task("Pending"));
+        model.add(task("Pending too"));
+        model.toggle(done_id).unwrap();
+        assert_eq!(model.completion_percent(), 33);
+    }
+
+    #[test]
+    fn parse_id_list_expands_ranges_and_dedupes() {
+        assert_eq!(parse_id_list("1-3,5, 8,2"), Ok(vec![1, 2, 3, 5, 8]));
+        assert_eq!(parse_id_list("3 5  8"), Ok(vec![3, 5, 8]));
+        assert!(parse_id_list("3-1").is_err());
+        assert!(parse_id_list("a,2").is_err());
+        assert!(parse_id_list(" , ").is_err());
+    }
+
+    #[test]
+    fn archive_many_reports_missing_ids() {
+        let mut model = empty_model("archive_many");
+        let (first, second) = (task("First"), task("Second"));
+        let ids = [first.id, second.id, u32::MAX];
+        model.add(first);
+        model.add(second);
+
+        assert_eq!(model.archive_many(&ids), (2, vec![u32::MAX]));
+        assert!(model.get_all().is_empty());
+        assert_eq!(model.get_archived().len(), 2);
+
+        model.restore(ids[0]).unwrap();
+        assert_eq!(model.get_all()[0].id, ids[0]);
+        assert!(model.restore(ids[0]).is_err());
+    }
+
+    #[test]
+    fn config_hides_menu_items_and_sets_sort() {
+        let config = Config::parse("# comment\nshow_clear_all = false\ndefault_sort = \"due\"\n");
+        assert!(!config.menu_items().contains(&MenuAction::ClearAll));
+        assert_eq!(config.menu_items().len(), MenuAction::ALL.len() - 1);
+        assert!(config.auto_sort == SortOrder::DueDate);
+    }
+
+    #[test]
+    fn check_length_counts_chars_not_bytes() {
+        assert!(check_length("Title", &"é".repeat(MAX_TITLE_LEN), MAX_TITLE_LEN).is_ok());
+        assert_eq!(
+            check_length("Description", &"x".repeat(501), MAX_DESCRIPTION_LEN),
+            Err("Description too long (501 chars, max 500).".to_string())
+        );
+    }
+
+    #[test]
+    fn relative_age_picks_the_largest_unit() {
+        let now = 1_000_000;
+        assert_eq!(date_utils::relative_age(now - 30, now), "just now");
+        assert_eq!(date_utils::relative_age(now - 60, now), "1 minute ago");
+        assert_eq!(date_utils::relative_age(now - 5 * 60, now), "5 minutes ago");
+        assert_eq!(
+            date_utils::relative_age(now - 2 * 3_600, now),
+            "2 hours ago"
+        );
+        assert_eq!(
+            date_utils::relative_age(now - 3 * 86_400, now),
+            "3 days ago"
+        );
+    }
+
+    #[test]
+    fn presenter_adds_task_from_scripted_input() {
+        let (mut presenter, output) = scripted_presenter(
+            "presenter_add",
+            &[
+                "2",
+                "Buy milk",
+                ".",
+                "2024-06-12",
+                "high",
+                "",
+                "",
+                "",
+                "",
+                "",
+                "0",
+            ],
+        );
+        presenter.interaction_loop();
+
+        let tasks = presenter.lists.active().get_all();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].title, "Buy milk");
+        let expected = format!(
+            "Added task #{}: 'Buy milk' (due 2024-06-12, priority High)",
+            tasks[0].id
+        );
+        assert!(output.borrow().contains(&expected));
+    }
+
+    #[test]
+    fn presenter_reports_unknown_id() {
+        let (mut presenter, output) = scripted_presenter("presenter_unknown", &["3", "4242"]);
+        presenter.interaction_loop();
+
+        let output = output.borrow();
+        assert!(
+            output
+                .iter()
+                .any(|line| line == "Task with id 4242 not found. No tasks exist.")
+        );
+    }
+
+    #[test]
+    fn add_note_prefixes_a_timestamp() {
+        let mut model = empty_model("add_note");
+        let item = task("Report");
+        let id = item.id;
+        model.add(item);
+
+        model.add_note(id, "sent draft".to_string()).unwrap();
+        let note = &model.get(id).unwrap().notes[0];
+        assert!(note.starts_with('['));
+        assert!(note.ends_with("] sent draft"));
+        assert!(model.add_note(u32::MAX, "x".to_string()).is_err());
+    }
+
+    #[test]
+    fn split_command_line_honours_quotes() {
+        assert_eq!(
+            split_command_line(r#"add "Buy milk" 'two litres'"#),
+            Ok(vec![
+                "add".to_string(),
+                "Buy milk".to_string(),
+                "two litres".to_string()
+            ])
+        );
+        assert!(split_command_line("add \"oops").is_err());
+    }
+
+    #[test]
+    fn format_minutes_splits_hours() {
+        assert_eq!(date_utils::format_minutes(45), "45m");
+        assert_eq!(date_utils::format_minutes(120), "2h");
+        assert_eq!(date_utils::format_minutes(270), "4h 30m");
+    }
+
+    #[test]
+    fn search_ignores_accents() {
+        let mut model = empty_model("search_accents");
+        model.add(task("Café with Zoë"));
+        model.add(task("Tea"));
+        assert_eq!(model.search("cafe").len(), 1);
+        assert_eq!(model.search("ZOE").len(), 1);
+        assert_eq!(model.search("café").len(), 1);
+    }
+
+    #[test]
+    fn presenter_accepts_letter_shortcuts() {
+        let (mut presenter, _) = scripted_presenter(
+            "presenter_shortcut",
+            &["a", "Walk", ".", "", "", "", "", "", "", "q"],
+        );
+        presenter.interaction_loop();
+
+        assert_eq!(presenter.lists.active().get_all().len(), 1);
+    }
+
+    #[test]
+    fn load_migrates_bare_arrays_and_guards_newer_versions() {
+        let path = temp_path("versioned");
+        let _cleanup = TempFile(path.clone());
+        fs::write(
+            &path,
+            r#"[{"id":7,"title":"Old","description":"","date":"","done":false}]"#,
+        )
+        .unwrap();
+        let model = TasksModel::load_from(&path);
+        assert_eq!(model.get_all()[0].title, "Old");
+        model.save().unwrap();
+        let saved = json::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            saved.get("version").and_then(json::Value::as_u64),
+            Some(FORMAT_VERSION)
+        );
+
+        fs::write(&path, r#"{"version":99,"tasks":[]}"#).unwrap();
+        assert!(TasksModel::load_from(&path).save().is_err());
+    }
+
+    #[test]
+    fn date_only_due_is_not_overdue_until_end_of_day() {
+        let mut item = task("Meeting");
+        let day = date_utils::parse_date("2030-05-01").unwrap() as u64;
+        let morning = day * 86_400 + 9 * 3_600;
+        item.due_date = Some("2030-05-01".to_string());
+        assert!(!item.is_overdue(morning));
+        item.due_date = Some("2030-05-01 08:30".to_string());
+        assert!(item.is_overdue(morning));
+    }
+
+    #[test]
+    fn dependencies_block_completion_and_reject_cycles() {
+        let mut model = empty_model("dependencies");
+        let (first, second) = (task("Design"), task("Build"));
+        let (first_id, second_id) = (first.id, second.id);
+        model.add(first);
+        model.add(second);
+
+        model.set_dependency(second_id, Some(first_id)).unwrap();
+        assert!(model.mark_done(second_id).is_err());
+        assert!(model.set_dependency(first_id, Some(second_id)).is_err());
+
+        model.mark_done(first_id).unwrap();
+        model.mark_done(second_id).unwrap();
+    }
+
+    #[test]
+    fn completion_time_is_set_and_cleared() {
+        let mut model = empty_model("completed_at");
+        let item = task("Ship");
+        let id = item.id;
+        model.add(item);
+
+        let before = date_utils::now_secs();
+        model.mark_done(id).unwrap();
+        assert_eq!(model.completed_between(before, u64::MAX).len(), 1);
+        assert!(model.completed_between(0, before).is_empty());
+        model.mark_not_done(id).unwrap();
+        assert_eq!(model.get(id).unwrap().completed_at, None);
+    }
+
+    #[test]
+    fn report_covers_every_day_in_the_span() {
+        let mut model = empty_model("report");
+        let item = task("Done today");
+        let id = item.id;
+        model.add(item);
+        model.mark_done(id).unwrap();
+
+        let report = model.report_last_days(7);
+        assert_eq!(report.len(), 7);
+        assert_eq!(report[6], (date_utils::format_date(date_utils::today()), 1));
+        assert!(report[..6].iter().all(|(_, count)| *count == 0));
+    }
+
+    #[test]
+    fn task_menu_exits_when_task_is_deleted() {
+        let (mut presenter, output) = scripted_presenter("task_menu", &["5"]);
+        let item = task("Temporary");
+        let id = item.id;
+        presenter.lists.active_mut().add(item);
+        presenter.task_menu(id).unwrap();
+
+        let expected = format!("Task {} no longer exists.", id);
+        assert!(output.borrow().contains(&expected));
+    }
+
+    #[test]
+    fn task_menu_edits_wait_for_exit_without_autosave() {
+        let item = task("Draft");
+        let id = item.id;
+        let (mut presenter, _) = scripted_presenter("task_menu_no_autosave", &["2", "b"]);
+        presenter.autosave = false;
+        presenter.lists.active_mut().add(item);
+        presenter.task_menu(id).unwrap();
+
+        assert!(presenter.lists.active().get(id).unwrap().done);
+        assert!(!temp_path("task_menu_no_autosave").exists());
+    }
+
+    #[test]
+    fn date_format_supports_common_specifiers() {
+        let secs = date_utils::parse_datetime("2024-06-10 09:15").unwrap();
+        assert_eq!(
+            date_utils::format_with(secs, "%d/%m/%Y %H:%M"),
+            "10/06/2024 09:15"
+        );
+        assert!(!date_utils::is_valid_format("%Q"));
+        let config = Config::parse("date_format = \"%x\"\n");
+        assert_eq!(config.date_format, DEFAULT_DATE_FORMAT);
+    }
+
+    #[test]
+    fn corrupt_file_is_backed_up_and_model_starts_empty() {
+        let path = temp_path("corrupt");
+        let backup = temp_path("corrupt").with_extension("json.bak");
+        let _ = fs::remove_file(&backup);
+        let _cleanup = [TempFile(path.clone()), TempFile(backup.clone())];
+        fs::write(&path, [0xff, 0x00, b'{', b'"']).unwrap();
+
+        let model = TasksModel::load_from(&path);
+        assert!(model.get_all().is_empty());
+        assert!(backup.exists());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn presenter_flushes_changes_on_end_of_input() {
+        let (mut presenter, _) = scripted_presenter(
+            "presenter_flush",
+            &["2", "Flushed", ".", "", "", "", "", "", ""],
+        );
+        presenter.interaction_loop();
+
+        assert!(!presenter.lists.active().is_dirty());
+        let reloaded = TasksModel::load_from(temp_path("presenter_flush"));
+        assert_eq!(reloaded.get_all().len(), 1);
+    }
+
+    #[test]
+    fn list_manager_switches_between_lists() {
+        let mut lists = ListManager::with_model(DEFAULT_LIST, empty_model("lists_default"));
+        lists
+            .lists
+            .insert("work".to_string(), empty_model("lists_work"));
+        lists.switch("work").unwrap();
+        lists.active_mut().add(task("Report"));
+        lists.switch(DEFAULT_LIST).unwrap();
+
+        assert!(lists.active().get_all().is_empty());
+        assert_eq!(lists.lists["work"].get_all().len(), 1);
+        assert!(lists.switch("missing").is_err());
+        assert!(lists.delete(DEFAULT_LIST).is_err());
+        assert!(lists.check_name("bad name").is_err());
+    }
+
+    #[test]
+    fn quick_add_extracts_tags_priority_and_due() {
+        let task = parse_quick_add("Buy milk @home !high due:2024-06-12 !nope").into_task();
+        assert_eq!(task.title, "Buy milk !nope");
+        assert_eq!(task.tags, vec!["home".to_string()]);
+        assert_eq!(task.priority, Priority::High);
+        assert_eq!(task.due_date.as_deref(), Some("2024-06-12"));
+    }
+
+    #[test]
+    fn get_id_input_retries_and_cancels() {
+        let view = |input: &[&str]| {
+            CliView::with_io(Box::new(ScriptedIo {
+                input: input.iter().map(|line| line.to_string()).collect(),
+                output: Rc::new(RefCell::new(Vec::new())),
+                _cleanup: None,
+            }))
+        };
+        assert_eq!(view(&["x", "y", "7"]).get_id_input("Id:"), Some(7));
+        assert_eq!(view(&["x", ""]).get_id_input("Id:"), None);
+        assert_eq!(view(&["a", "b", "c", "d", "5"]).get_id_input("Id:"), None);
+    }
+
+    #[test]
+    fn templates_round_trip_and_instantiate_with_fresh_ids() {
+        let path = temp_path("templates");
+        let _cleanup = TempFile(path.clone());
+        let mut model = TasksModel::load_from(&path);
+        let mut source = task("Weekly report");
+        source.tags = vec!["work".to_string()];
+        let source_id = source.id;
+        model.add(source);
+        model.save_template("weekly", source_id).unwrap();
+        model.save().unwrap();
+
+        let mut model = TasksModel::load_from(&path);
+        assert_eq!(model.template_names(), vec!["weekly"]);
+        let id = model.add_from_template("weekly").unwrap();
+        let created = model.get(id).unwrap();
+        assert_ne!(id, source_id);
+        assert_eq!(created.tags, vec!["work".to_string()]);
+        assert_eq!(
+            created.due_date,
+            Some(date_utils::format_date(date_utils::today()))
+        );
+        assert!(model.add_from_template("missing").is_err());
+    }
+
+    #[test]
+    fn completions_after_last_opened_are_reported() {
+        let path = temp_path("last_opened");
+        let _cleanup = TempFile(path.clone());
+        let mut model = TasksModel::load_from(&path);
+        let mut before = task("Before");
+        before.done = true;
+        before.completed_at = Some(100);
+        let mut after = task("After");
+        after.done = true;
+        after.completed_at = Some(300);
+        model.add(before);
+        model.add(after);
+        assert!(model.completed_since_last_run().is_empty());
+        model.set_last_opened(200);
+        model.save().unwrap();
+
+        let model = TasksModel::load_from(&path);
+        let titles: Vec<&str> = model
+            .completed_since_last_run()
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["After"]);
+    }
+
+    #[test]
+    fn completing_last_subtask_offers_to_finish_parent() {
+        let mut parent = task("Move house");
+        parent.subtasks = vec![("Pack".to_string(), true), ("Drive".to_string(), false)];
+        let id = parent.id;
+        let input = id.to_string();
+        let (mut presenter, output) = scripted_presenter("subtasks", &[&input, "2", "y"]);
+        presenter.lists.active_mut().add(parent);
+        assert!(presenter.lists.active_mut().toggle_subtask(id, 3).is_err());
+        presenter.toggle_subtask().unwrap();
+        assert!(presenter.lists.active().get(id).unwrap().done);
+        assert!(
+            output
+                .borrow()
+                .iter()
+                .any(|line| line.contains("Marked task"))
+        );
+    }
+
+    #[test]
+    fn repeat_last_add_prefills_previous_values() {
+        let (mut presenter, _output) = scripted_presenter(
+            "repeat_add",
+            &[
+                "Log reading",
+                "30 pages",
+                ".",
+                "",
+                "high",
+                "",
+                "books",
+                "",
+                "",
+                "",
+                "",
+                ".",
+                "",
+                "",
+                "",
+                "-",
+                "",
+                "",
+            ],
+        );
+        presenter.add_task().unwrap();
+        presenter.repeat_last_add().unwrap();
+        let tasks = presenter.lists.active().get_all();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[1].title, "Log reading");
+        assert_eq!(tasks[1].description, "30 pages");
+        assert_eq!(tasks[1].priority, Priority::High);
+        assert!(tasks[1].tags.is_empty());
+    }
+
+    #[test]
+    fn auto_sort_setting_is_rewritten_in_place() {
+        let content = "# prefs\nauto_sort = \"id\"\nshow_undo = false\n";
+        let updated = Config::with_setting(content, "auto_sort", SortOrder::Created.key());
+        assert_eq!(
+            updated,
+            "# prefs\nauto_sort = \"created\"\nshow_undo = false\n"
+        );
+        assert!(Config::parse(&updated).auto_sort == SortOrder::Created);
+        let appended = Config::with_setting("", "auto_sort", "none");
+        assert!(Config::parse(&appended).auto_sort == SortOrder::Manual);
+    }
+
+    #[test]
+    fn sort_wrappers_match_the_shared_comparators() {
+        let mut model = empty_model("sort_wrappers");
+        let mut late = task("Late");
+        late.due_date = Some("2030-01-02".to_string());
+        let mut urgent = task("Urgent");
+        urgent.due_date = Some("2030-01-01".to_string());
+        urgent.priority = Priority::High;
+        model.add(late);
+        model.add(urgent);
+        model.add(task("Undated"));
+        let titles = |tasks: Vec<&Task>| -> Vec<String> {
+            tasks.iter().map(|task| task.title.clone()).collect()
+        };
+        assert_eq!(titles(model.sorted_by_due()), ["Urgent", "Late", "Undated"]);
+        assert_eq!(
+            titles(model.sorted_by_priority()),
+            titles(model.sorted(SortOrder::Priority))
+        );
+        assert_eq!(titles(model.sorted_by_id()), ["Late", "Urgent", "Undated"]);
+    }
+
+    #[test]
+    fn bulk_tag_with_blank_query_tags_every_task() {
+        let (mut presenter, output) = scripted_presenter("bulk_tag_all", &["", "errand"]);
+        presenter.lists.active_mut().add(task("Post office"));
+        presenter.lists.active_mut().add(task("Bank"));
+        presenter.bulk_tag().unwrap();
+
+        let tasks = presenter.lists.active().get_all();
+        assert!(tasks.iter().all(|task| task.tags == ["errand"]));
+        assert!(
+            output
+                .borrow()
+                .contains(&"Added tag 'errand' to 2 task(s).".to_string())
+        );
+    }
+
+    #[test]
+    fn bulk_tagging_skips_tasks_that_already_match() {
+        let mut model = empty_model("bulk_tag");
+        let mut tagged = task("Tagged");
+        tagged.tags = vec!["Work".to_string()];
+        let plain = task("Plain");
+        let ids = vec![tagged.id, plain.id];
+        model.add(tagged);
+        model.add(plain);
+        assert_eq!(model.add_tag_to_many(&ids, "work"), 1);
+        assert_eq!(model.add_tag_to_many(&ids, "work"), 0);
+        assert!(model.get_all().iter().all(|task| task.tags.len() == 1));
+        assert_eq!(model.remove_tag_from_many(&ids, "WORK"), 2);
+        assert!(model.get_all().iter().all(|task| task.tags.is_empty()));
+    }
+
+    #[test]
+    fn exit_without_autosave_asks_before_saving() {
+        let path = temp_path("exit_confirm");
+        let _ = fs::remove_file(&path);
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let io = ScriptedIo {
+            input: ["q", "maybe", "n"]
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect(),
+            output: Rc::clone(&output),
+            _cleanup: Some(TempFile(path.clone())),
+        };
+        let mut model = TasksModel::load_from(&path);
+        model.add(task("Unsaved"));
+        let lists = ListManager::with_model(DEFAULT_LIST, model);
+        let config = Config {
+            autosave: false,
+            ..Config::default()
+        };
+        let mut presenter = Presenter::new(lists, CliView::with_io(Box::new(io)), &config);
+        presenter.interaction_loop();
+        assert!(
+            output
+                .borrow()
+                .iter()
+                .any(|line| line.contains("Please answer y or n."))
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn snooze_advances_or_sets_the_due_date() {
+        assert_eq!(
+            date_utils::parse_duration("1w"),
+            Some(Duration::from_secs(7 * 86_400))
+        );
+        assert_eq!(date_utils::parse_duration("3x"), None);
+        assert_eq!(date_utils::parse_duration("0d"), None);
+        assert_eq!(date_utils::parse_duration("00h"), None);
+        let mut model = empty_model("snooze");
+        let mut dated = task("Dated");
+        dated.due_date = Some("2024-01-30".to_string());
+        dated.done = true;
+        let timed = {
+            let mut timed = task("Timed");
+            timed.due_date = Some("2024-01-30 09:15".to_string());
+            timed
+        };
+        let undated = task("Undated");
+        let ids = [dated.id, timed.id, undated.id];
+        model.add(dated);
+        model.add(timed);
+        model.add(undated);
+        let three_days = date_utils::parse_duration("3d").unwrap();
+        for id in ids {
+            model.snooze(id, three_days).unwrap();
+        }
+        let due = |id| model.get(id).unwrap().due_date.clone().unwrap();
+        assert_eq!(due(ids[0]), "2024-02-02");
+        assert_eq!(due(ids[1]), "2024-02-02 09:15");
+        assert_eq!(
+            due(ids[2]),
+            date_utils::format_date(date_utils::today() + 3)
+        );
+        assert!(model.get(ids[0]).unwrap().done);
+    }
+
+    #[test]
+    fn display_fields_pick_and_order_task_fields() {
+        let config = Config::parse("display_fields = \"title, bogus, id\"\n");
+        assert_eq!(
+            config.display_fields,
+            vec![DisplayField::Title, DisplayField::Id]
+        );
+        let (presenter, output) = scripted_presenter("display_fields", &[]);
+        let mut view = presenter.view;
+        view.display_fields = config.display_fields;
+        let mut item = task("Water plants");
+        item.description = "Hidden".to_string();
+        view.display_task(&item, None);
+        let first = output.borrow()[0].clone();
+        assert!(first.starts_with(&format!("title: Water plants | id: {}", item.id)));
+        assert!(!first.contains("Hidden"));
+    }
+
+    #[test]
+    fn overdue_count_ignores_done_and_future_tasks() {
+        let mut model = empty_model("overdue_count");
+        for (due, done) in [
+            ("2020-01-01", false),
+            ("2020-01-01", true),
+            ("2999-01-01", false),
+        ] {
+            let mut item = task("Dated");
+            item.due_date = Some(due.to_string());
+            item.done = done;
+            model.add(item);
+        }
+        model.add(task("Undated"));
+        assert_eq!(model.overdue_count(), 1);
+    }
+
+    #[test]
+    fn import_lines_skips_blank_lines() {
+        let path = temp_path("import_lines");
+        let _cleanup = TempFile(path.clone());
+        fs::write(&path, "Buy milk\n\n   \n  Call mum  \n").unwrap();
+        let mut model = empty_model("import_lines_model");
+        let count = model.import_lines(path.to_str().unwrap()).unwrap();
+        assert_eq!(count, 2);
+        let titles: Vec<&str> = model
+            .get_all()
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Buy milk", "Call mum"]);
+        assert!(model.get_all().iter().all(|task| task.due_date.is_none()));
+    }
+
+    #[test]
+    fn clear_all_only_confirms_above_the_threshold() {
+        let (mut presenter, output) = scripted_presenter("clear_threshold", &["n"]);
+        presenter.lists.active_mut().add(task("Only one"));
+        presenter.delete_tasks().unwrap();
+        assert!(presenter.lists.active().get_all().is_empty());
+        presenter.lists.active_mut().add(task("First"));
+        presenter.lists.active_mut().add(task("Second"));
+        presenter.delete_tasks().unwrap();
+        assert_eq!(presenter.lists.active().get_all().len(), 2);
+        assert!(
+            output
+                .borrow()
+                .iter()
+                .any(|line| line == "Cleared 1 tasks.")
+        );
+        assert!(
+            output
+                .borrow()
+                .contains(&"Are you sure? (y/N):".to_string())
+        );
+        assert_eq!(presenter.lists.active().get_archived()[0].title, "Only one");
+    }
+
+    #[test]
+    fn clear_all_archives_and_undo_restores_the_order() {
+        let (mut presenter, _) = scripted_presenter("clear_archive", &["yes"]);
+        for title in ["First", "Second", "Third"] {
+            presenter.lists.active_mut().add(task(title));
+        }
+        presenter.delete_tasks().unwrap();
+        assert!(presenter.lists.active().get_all().is_empty());
+        assert_eq!(presenter.lists.active().get_archived().len(), 3);
+        presenter.undo().unwrap();
+        let titles: Vec<&str> = presenter
+            .lists
+            .active()
+            .get_all()
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(titles, ["First", "Second", "Third"]);
+        assert!(presenter.lists.active().get_archived().is_empty());
+    }
+
+    #[test]
+    fn delete_can_pick_a_task_by_position() {
+        let (mut presenter, _output) = scripted_presenter("pick_task", &["p", "2"]);
+        let first = task("First");
+        let second = task("Second");
+        let (first_id, second_id) = (first.id, second.id);
+        presenter.lists.active_mut().add(first);
+        presenter.lists.active_mut().add(second);
+        presenter.delete_task().unwrap();
+        assert!(presenter.lists.active().get(first_id).is_some());
+        assert!(presenter.lists.active().get(second_id).is_none());
+        assert_eq!(presenter.view.pick_task(&[]), None);
+    }
+
+    #[test]
+    fn links_must_be_http_urls() {
+        let mut model = empty_model("links");
+        let item = task("Review ticket");
+        let id = item.id;
+        model.add(item);
+        assert!(model.set_link(id, Some("example.com".to_string())).is_err());
+        assert!(model.set_link(id, Some("https://".to_string())).is_err());
+        model
+            .set_link(id, Some("https://example.com/t/1".to_string()))
+            .unwrap();
+        let restored = Task::from_json(&model.get(id).unwrap().to_json()).unwrap();
+        assert_eq!(restored.link.as_deref(), Some("https://example.com/t/1"));
+    }
+
+    #[test]
+    fn adding_stops_at_the_configured_task_limit() {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let io = ScriptedIo {
+            input: VecDeque::from(vec!["Second\n".to_string()]),
+            output: Rc::clone(&output),
+            _cleanup: Some(TempFile(temp_path("max_tasks"))),
+        };
+        let config = Config {
+            max_tasks: 1,
+            ..Config::default()
+        };
+        let lists = ListManager::with_model(DEFAULT_LIST, empty_model("max_tasks"));
+        let mut presenter = Presenter::new(lists, CliView::with_io(Box::new(io)), &config);
+        presenter.lists.active_mut().add(task("First"));
+        let error = presenter.add_task().unwrap_err();
+        assert!(error.contains("limit of 1 tasks"));
+        assert!(presenter.quick_add().is_err());
+        assert_eq!(presenter.lists.active().get_all().len(), 1);
+    }
+
+    #[test]
+    fn task_limit_also_covers_commands_imports_and_recurrence() {
+        let mut model = empty_model("max_tasks_cli");
+        let _cleanup = TempFile(temp_path("max_tasks_cli"));
+        model.max_tasks = 1;
+        let args = |line: &str| split_command_line(line).unwrap();
+        run_command(&mut model, &args("add First")).unwrap();
+        let error = run_command(&mut model, &args("add Second")).unwrap_err();
+        assert!(error.contains("limit of 1 tasks"));
+        assert!(run_command(&mut model, &args("quick Third @home")).is_err());
+        assert_eq!(model.get_all().len(), 1);
+
+        let id = model.get_all()[0].id;
+        model.tasks[0].recurrence = Some(Recurrence::Daily);
+        assert!(model.mark_done(id).unwrap_err().contains("next occurrence"));
+        assert!(model.get(id).unwrap().done);
+        assert_eq!(model.get_all().len(), 1);
+    }
+
+    #[test]
+    fn next_focus_prefers_priority_then_due_date() {
+        let mut model = empty_model("focus");
+        let mut later = task("Later");
+        later.priority = Priority::High;
+        later.due_date = Some("2030-05-02".to_string());
+        let mut sooner = task("Sooner");
+        sooner.priority = Priority::High;
+        sooner.due_date = Some("2030-05-01".to_string());
+        let mut done = task("Done");
+        done.priority = Priority::High;
+        done.done = true;
+        model.add(task("Medium"));
+        model.add(later);
+        model.add(sooner);
+        model.add(done);
+        assert_eq!(model.next_focus().unwrap().title, "Sooner");
+        for task in &mut model.tasks {
+            task.done = true;
+        }
+        assert!(model.next_focus().is_none());
+    }
+
+    #[test]
+    fn json_listing_is_a_plain_array() {
+        let mut model = empty_model("list_json");
+        assert_eq!(json::stringify(&model.tasks_json()), "[]");
+        model.add(task("Ship \"it\""));
+        let parsed = json::parse(&json::stringify(&model.tasks_json())).unwrap();
+        let items = parsed.as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0].get("title").and_then(json::Value::as_str),
+            Some("Ship \"it\"")
+        );
+    }
+
+    #[test]
+    fn old_completions_are_archived_automatically() {
+        let now = date_utils::now_secs();
+        let finished = |title: &str, days_ago: u64| {
+            let mut item = task(title);
+            item.done = true;
+            item.completed_at = Some(now - days_ago * date_utils::SECS_PER_DAY);
+            item
+        };
+        let mut lists = ListManager::with_model(DEFAULT_LIST, empty_model("auto_archive"));
+        lists.active_mut().add(finished("Old", 10));
+        lists.active_mut().add(finished("Recent", 1));
+        lists.active_mut().add(task("Pending"));
+        let mut work = empty_model("auto_archive_work");
+        work.add(finished("Old report", 30));
+        lists.lists.insert("work".to_string(), work);
+
+        let config = Config::parse("auto_archive_done_after_days = 7\n");
+        assert_eq!(lists.auto_archive(0, now), 0);
+        assert_eq!(
+            lists.auto_archive(config.auto_archive_done_after_days, now),
+            2
+        );
+        assert_eq!(lists.active().get_archived()[0].title, "Old");
+        assert_eq!(lists.active().get_all().len(), 2);
+        assert_eq!(lists.lists["work"].get_archived()[0].title, "Old report");
+    }
+
+    #[test]
+    fn effective_priority_escalates_near_the_deadline() {
+        let now = 1_700_000_000;
+        let due_in = |hours: u64, priority: Priority, done: bool| {
+            let mut item = task("Deadline");
+            item.due_date = Some(date_utils::format_datetime(now + hours * 3_600));
+            item.priority = priority;
+            item.done = done;
+            effective_priority(&item, now)
+        };
+        assert_eq!(due_in(12, Priority::Low, false), Priority::High);
+        assert_eq!(due_in(48, Priority::Low, false), Priority::Medium);
+        assert_eq!(due_in(48, Priority::High, false), Priority::High);
+        assert_eq!(due_in(200, Priority::Low, false), Priority::Low);
+        assert_eq!(due_in(12, Priority::Low, true), Priority::Low);
+    }
+
+    #[test]
+    fn delete_by_tag_archives_only_tagged_tasks() {
+        let mut model = empty_model("delete_by_tag");
+        let mut tagged = task("Launch");
+        tagged.tags = vec!["Project-X".to_string()];
+        model.add(tagged);
+        model.add(task("Unrelated"));
+        assert_eq!(model.delete_by_tag("project-x"), 1);
+        assert_eq!(model.delete_by_tag("project-x"), 0);
+        assert_eq!(model.get_all()[0].title, "Unrelated");
+        assert_eq!(model.get_archived()[0].title, "Launch");
+    }
+
+    #[test]
+    fn failed_save_can_retry_then_write_elsewhere() {
+        let blocked = temp_path("save_retry_dir");
+        let alternate = temp_path("save_retry_copy");
+        let _cleanup = [TempFile(blocked.clone()), TempFile(alternate.clone())];
+        fs::create_dir_all(&blocked).unwrap();
+        fs::create_dir_all(&alternate).unwrap();
+        let alternate_input = alternate.to_str().unwrap().to_string();
+        let (mut presenter, output) =
+            scripted_presenter("save_retry", &["", "n", &alternate_input]);
+        let mut model = TasksModel::load_from(&blocked);
+        model.add(task("Keep me"));
+        let mut work = TasksModel::load_from(&blocked);
+        work.add(task("Work too"));
+        presenter.lists = ListManager::with_model(DEFAULT_LIST, model);
+        presenter.lists.lists.insert("work".to_string(), work);
+        presenter.flush().unwrap();
+        let retries = output
+            .borrow()
+            .iter()
+            .filter(|line| line.starts_with("Failed to save:"))
+            .count();
+        assert_eq!(retries, 2);
+        assert!(!presenter.lists.is_dirty());
+        let copy = TasksModel::load_from(alternate.join("default.json"));
+        assert_eq!(copy.get_all()[0].title, "Keep me");
+        let copy = TasksModel::load_from(alternate.join("work.json"));
+        assert_eq!(copy.get_all()[0].title, "Work too");
+        let mut leftover = blocked.clone().into_os_string();
+        leftover.push(".tmp");
+        assert!(!PathBuf::from(leftover).exists());
+    }
+
+    #[test]
+    fn pinned_tasks_lead_listings_and_survive_reload() {
+        let path = temp_path("pinned");
+        let _cleanup = TempFile(path.clone());
+        let mut model = TasksModel::load_from(&path);
+        let first = task("First");
+        let second = task("Second");
+        let (first_id, second_id) = (first.id, second.id);
+        model.add(first);
+        model.add(second);
+        assert!(model.toggle_pin(second_id).unwrap());
+        model.save().unwrap();
+        let model = TasksModel::load_from(&path);
+        assert!(model.get(second_id).unwrap().pinned);
+
+        let (presenter, output) = scripted_presenter("pinned_view", &[]);
+        let tasks: Vec<&Task> = model.get_all().iter().collect();
+        presenter.view.display_tasks(&tasks, model.get_all());
+        let headers: Vec<String> = output
+            .borrow()
+            .iter()
+            .filter(|line| line.contains("title:"))
+            .cloned()
+            .collect();
+        assert!(headers[0].starts_with("📌 ") && headers[0].contains("Second"));
+        let plain: Vec<u32> = model
+            .display_order(SortOrder::Manual)
+            .iter()
+            .map(|task| task.id)
+            .collect();
+        assert_eq!(plain, vec![second_id, first_id]);
+    }
+
+    #[test]
+    fn ascii_only_replaces_emoji_in_task_output() {
+        let (mut presenter, output) = scripted_presenter("ascii_only", &[]);
+        presenter
+            .view
+            .apply_config(&Config::parse("ascii_only = true\n"));
+        let mut item = task("Plain");
+        item.description = "Details".to_string();
+        item.tags = vec!["home".to_string()];
+        presenter.view.display_task(&item, None);
+        let text = output.borrow().join("\n");
+        assert!(text.is_ascii(), "{}", text);
+        assert!(text.contains("status: [ ] Not done"));
+        assert!(text.contains(" note: Details"));
+        assert!(text.contains(" tags: home"));
+        assert!(text.contains("no due date | created:"));
+    }
+
+    #[test]
+    fn compact_ids_renumbers_in_order_and_remaps_dependencies() {
+        let mut model = empty_model("compact_ids");
+        let blocker = task("Blocker");
+        let mut blocked = task("Blocked");
+        blocked.blocked_by = Some(blocker.id);
+        let archived = task("Archived");
+        let archived_id = archived.id;
+        model.add(blocked);
+        model.add(blocker);
+        model.add(archived);
+        model.archive(archived_id).unwrap();
+        model.compact_ids(SortOrder::Manual);
+        let ids: Vec<u32> = model.get_all().iter().map(|task| task.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(model.get_all()[0].title, "Blocked");
+        assert_eq!(model.get_all()[0].blocked_by, Some(2));
+        assert_eq!(model.get_archived()[0].id, 3);
+
+        model.set_priority(2, Priority::High).unwrap();
+        model.compact_ids(SortOrder::Priority);
+        let numbered = |model: &TasksModel| -> Vec<(u32, String)> {
+            model
+                .get_all()
+                .iter()
+                .map(|task| (task.id, task.title.clone()))
+                .collect()
+        };
+        assert_eq!(
+            numbered(&model),
+            vec![(2, "Blocked".to_string()), (1, "Blocker".to_string())]
+        );
+        assert_eq!(model.get_all()[0].blocked_by, Some(1));
+
+        model.toggle_pin(2).unwrap();
+        model.compact_ids(SortOrder::Priority);
+        assert_eq!(
+            numbered(&model),
+            vec![(1, "Blocked".to_string()), (2, "Blocker".to_string())]
+        );
+    }
+
+    #[test]
+    fn due_between_is_inclusive_and_skips_undated_tasks() {
+        let mut model = empty_model("due_between");
+        for due in ["2024-06-09", "2024-06-10", "2024-06-16 18:00", "2024-06-17"] {
+            let mut item = task(due);
+            item.due_date = Some(due.to_string());
+            model.add(item);
+        }
+        model.add(task("Undated"));
+        let titles: Vec<&str> = model
+            .due_between("2024-06-10", "2024-06-16")
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["2024-06-10", "2024-06-16 18:00"]);
+        assert!(model.due_between("junk", "2024-06-16").is_empty());
+
+        let (mut presenter, output) = scripted_presenter(
+            "due_between_prompt",
+            &["2024-06-10", "2024-06-01", "2024-06-11"],
+        );
+        presenter.due_between().unwrap();
+        assert!(
+            output
+                .borrow()
+                .iter()
+                .any(|line| line.contains("must not be before"))
+        );
+    }
+
+    #[test]
+    fn oldest_pending_ranks_snoozes_then_age() {
+        let mut model = empty_model("oldest_pending");
+        let mut old = task("Old");
+        old.created_at = 100;
+        let mut older = task("Older");
+        older.created_at = 50;
+        let snoozed = task("Snoozed");
+        let snoozed_id = snoozed.id;
+        let mut done = task("Done");
+        done.created_at = 1;
+        done.done = true;
+        model.add(old);
+        model.add(older);
+        model.add(snoozed);
+        model.add(done);
+        model
+            .snooze(snoozed_id, Duration::from_secs(date_utils::SECS_PER_DAY))
+            .unwrap();
+        let titles: Vec<&str> = model
+            .oldest_pending(2)
+            .iter()
+            .map(|task| task.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Snoozed", "Older"]);
+        assert_eq!(model.get(snoozed_id).unwrap().snooze_count, 1);
+    }
+
+    #[test]
+    fn toggle_accepts_space_separated_ids_and_reports_missing() {
+        let first = task("First");
+        let second = task("Second");
+        let input = format!("{} {} 999999", first.id, second.id);
+        let (mut presenter, output) = scripted_presenter("toggle_many", &[&input]);
+        let ids = [first.id, second.id];
+        presenter.lists.active_mut().add(first);
+        presenter.lists.active_mut().add(second);
+        let error = presenter.toggle_status().unwrap_err();
+        assert!(error.contains("999999"));
+        assert!(!output.borrow().contains(&"Your tasks".to_string()));
+        assert!(
+            ids.iter()
+                .all(|&id| presenter.lists.active().get(id).unwrap().done)
+        );
+    }
+
+    #[test]
+    fn due_soon_marks_pending_tasks_inside_the_window_only() {
+        let now = date_utils::now_secs();
+        let window = 2 * date_utils::SECS_PER_DAY;
+        let mut soon = task("Soon");
+        soon.due_date = Some(date_utils::format_date(date_utils::today() + 1));
+        let mut later = task("Later");
+        later.due_date = Some(date_utils::format_date(date_utils::today() + 5));
+        let mut late = task("Late");
+        late.due_date = Some(date_utils::format_date(date_utils::today() - 1));
+        assert!(soon.is_due_soon(now, window));
+        assert!(!later.is_due_soon(now, window));
+        assert!(!late.is_due_soon(now, window));
+        assert!(!task("Whenever").is_due_soon(now, window));
+
+        let (mut presenter, output) = scripted_presenter("due_soon", &[]);
+        presenter.view.display_task(&later, None);
+        assert!(!output.borrow().join("\n").contains("soon"));
+        presenter
+            .view
+            .apply_config(&Config::parse("due_soon_days = 7\n"));
+        presenter.view.display_task(&later, None);
+        assert!(output.borrow().join("\n").contains("🔜 soon"));
+        soon.done = true;
+        assert!(!soon.is_due_soon(now, window));
+    }
+
+    #[test]
+    fn failed_temp_write_leaves_the_data_file_untouched() {
+        let path = temp_path("atomic_save");
+        let mut temp = path.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        let _ = fs::remove_dir(&temp);
+        let _cleanup = TempFile(path.clone());
+        fs::write(&path, r#"{"version":1,"tasks":[]}"#).unwrap();
+        let original = fs::read_to_string(&path).unwrap();
+
+        let mut model = TasksModel::load_from(path.clone());
+        model.add(task("Never written"));
+        fs::create_dir(&temp).unwrap();
+        assert!(model.save().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+        fs::remove_dir(&temp).unwrap();
+        model.save().unwrap();
+        assert!(!temp.exists());
+        assert!(fs::read_to_string(&path).unwrap().contains("Never written"));
+    }
+
+    #[test]
+    fn completing_a_recurring_task_queues_one_next_occurrence() {
+        let mut model = empty_model("recurrence");
+        let mut item = task("Water plants");
+        item.due_date = Some("2024-01-31".to_string());
+        item.recurrence = Some(Recurrence::Monthly);
+        item.color_label = Some("green".to_string());
+        let id = item.id;
+        let mut namesake = item.clone();
+        namesake.id = id_generation::next();
+        let namesake_id = namesake.id;
+        model.add(item);
+        model.add(namesake);
+
+        model.mark_done(id).unwrap();
+        model.mark_not_done(id).unwrap();
+        model.mark_done(id).unwrap();
+        let reloaded = Task::from_json(&model.get(id).unwrap().to_json()).unwrap();
+        assert_eq!(
+            reloaded.next_occurrence_id,
+            model.get(id).unwrap().next_occurrence_id
+        );
+
+        let next: Vec<&Task> = model
+            .get_all()
+            .iter()
+            .filter(|task| task.id != id && task.id != namesake_id)
+            .collect();
+        assert_eq!(next.len(), 1);
+        assert!(!next[0].done);
+        assert_eq!(next[0].due_date.as_deref(), Some("2024-02-29"));
+        assert!(next[0].recurrence == Some(Recurrence::Monthly));
+        assert_eq!(next[0].color_label.as_deref(), Some("green"));
+
+        // A separate task with the same title still gets its own next occurrence.
+        model.mark_done(namesake_id).unwrap();
+        assert_eq!(model.get_all().len(), 4);
+    }
+
+    #[test]
+    fn today_view_lists_a_task_due_earlier_today_once() {
+        let (mut presenter, output) = scripted_presenter("today_once", &["y"]);
+        let mut item = task("Morning standup");
+        let midnight = date_utils::today() as u64 * date_utils::SECS_PER_DAY;
+        item.due_date = Some(date_utils::format_datetime(midnight));
+        presenter.lists.active_mut().add(item);
+        presenter.show_today().unwrap();
+
+        let text = output.borrow().join("\n");
+        assert_eq!(text.matches("Morning standup").count(), 1, "{}", text);
+    }
+
+    #[test]
+    fn blocked_tasks_are_not_remembered_as_completed() {
+        let blocker = task("Blocker");
+        let mut blocked = task("Blocked");
+        blocked.blocked_by = Some(blocker.id);
+        let (blocker_id, blocked_id) = (blocker.id, blocked.id);
+        let (mut presenter, _) =
+            scripted_presenter("remember_completed", &[&blocked_id.to_string(), "2", "b"]);
+        presenter.lists.active_mut().add(blocker);
+        presenter.lists.active_mut().add(blocked);
+        presenter.mark_done().unwrap();
+        assert_eq!(presenter.last_completed, None);
+
+        presenter.task_menu(blocker_id).unwrap();
+        assert_eq!(presenter.last_completed, Some(blocker_id));
+    }
+
+    #[test]
+    fn description_input_returns_the_editor_sentinel_immediately() {
+        let view = CliView::with_io(Box::new(ScriptedIo {
+            input: ["!edit", "High"]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+            output: Rc::new(RefCell::new(Vec::new())),
+            _cleanup: None,
+        }));
+        assert_eq!(view.get_description_input("Description:"), EDITOR_SENTINEL);
+        assert_eq!(view.get_user_input("Priority:").as_deref(), Some("High"));
+
+        let view = CliView::with_io(Box::new(ScriptedIo {
+            input: ["First", "!edit", "."]
+                .iter()
+                .map(|line| line.to_string())
+                .collect(),
+            output: Rc::new(RefCell::new(Vec::new())),
+            _cleanup: None,
+        }));
+        assert_eq!(view.get_description_input("Description:"), "First\n!edit");
+    }
+
+    #[test]
+    fn scripts_cannot_run_scripts() {
+        let script = temp_path("nested_script");
+        let script_arg = script.to_str().unwrap();
+        fs::write(
+            &script,
+            format!("--script \"{}\"\nadd Survivor\n", script_arg),
+        )
+        .unwrap();
+        let mut model = empty_model("nested_script_model");
+        let _cleanup = [
+            TempFile(script.clone()),
+            TempFile(temp_path("nested_script_model")),
+        ];
+
+        run_script(&mut model, script_arg, false).unwrap();
+        assert_eq!(model.get_all().len(), 1);
+        let error = run_script(&mut model, script_arg, true).unwrap_err();
+        assert_eq!(error, "Line 1: Scripts cannot run other scripts.");
+    }
+
+    #[test]
+    fn csv_round_trips_quotes_commas_and_newlines() {
+        let path = temp_path("csv_round_trip");
+        let _cleanup = TempFile(path.clone());
+        let path_arg = path.to_str().unwrap();
+        let mut model = empty_model("csv_round_trip_source");
+        let mut item = task("Say \"hi\", then leave");
+        item.description = "line one\nline two, with \"quotes\"".to_string();
+        item.done = true;
+        model.add(item);
+        model.export_csv(path_arg).unwrap();
+
+        let mut copy = empty_model("csv_round_trip_copy");
+        assert_eq!(copy.import_csv(path_arg), Ok(1));
+        let imported = &copy.get_all()[0];
+        assert_eq!(imported.title, "Say \"hi\", then leave");
+        assert_eq!(imported.description, "line one\nline two, with \"quotes\"");
+        assert!(imported.done);
+        assert!(imported.completed_at.is_some());
+    }
+
+    #[test]
+    fn csv_import_validates_titles_like_manual_adds() {
+        let path = temp_path("csv_titles");
+        let _cleanup = TempFile(path.clone());
+        let path_arg = path.to_str().unwrap();
+        let long = "x".repeat(MAX_TITLE_LEN + 1);
+        fs::write(
+            &path,
+            "id,title,description,date,done\n1,  ,,,false\n2,Kept,,,false\n",
+        )
+        .unwrap();
+        let mut model = empty_model("csv_titles_model");
+        assert_eq!(model.import_csv(path_arg), Ok(1));
+        assert_eq!(model.get_all()[0].title, "Kept");
+
+        fs::write(
+            &path,
+            format!("id,title,description,date,done\n1,{},,,false\n", long),
+        )
+        .unwrap();
+        let error = model.import_csv(path_arg).unwrap_err();
+        assert!(error.starts_with("Line 2: Title"), "{}", error);
+        assert_eq!(model.get_all().len(), 1);
+    }
+
+    #[test]
+    fn csv_import_reports_the_line_of_a_malformed_row() {
+        let path = temp_path("csv_malformed");
+        let _cleanup = TempFile(path.clone());
+        fs::write(
+            &path,
+            "id,title,description,date,done\n1,Ok,\"spans\ntwo lines\",,false\n2,Short,row\n",
+        )
+        .unwrap();
+        let mut model = empty_model("csv_malformed_model");
+        assert_eq!(
+            model.import_csv(path.to_str().unwrap()),
+            Err("Malformed row on line 4: expected 5 fields, found 3.".to_string())
+        );
+        assert!(model.get_all().is_empty());
+    }
+
+    #[test]
+    fn scratch_files_are_unique_and_removed_on_drop() {
+        let (first, _) = ScratchFile::create("rust_todo_scratch_test").unwrap();
+        let (second, _) = ScratchFile::create("rust_todo_scratch_test").unwrap();
+        assert_ne!(first.0, second.0);
+        let path = first.0.clone();
+        assert!(path.exists());
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn reopen_last_completed_keeps_the_handle_until_it_succeeds() {
+        let (mut presenter, _) = scripted_presenter("reopen_last", &[]);
+        let item = task("Finished");
+        let id = item.id;
+        presenter.lists.active_mut().add(item);
+        presenter.last_completed = Some(id);
+        assert!(
+            presenter
+                .reopen_last_completed()
+                .unwrap_err()
+                .contains("already open")
+        );
+        assert_eq!(presenter.last_completed, Some(id));
+
+        presenter.lists.active_mut().mark_done(id).unwrap();
+        presenter.reopen_last_completed().unwrap();
+        assert!(!presenter.lists.active().get(id).unwrap().done);
+        assert_eq!(presenter.last_completed, None);
+    }
+}